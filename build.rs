@@ -1,5 +1,4 @@
 use protobuf_codegen::Codegen;
-use protoc_bin_vendored;
 
 fn main() {
     Codegen::new()
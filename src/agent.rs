@@ -0,0 +1,402 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+use crate::{
+    error::Error,
+    prompt::ask_credentials,
+    types::{KeyFile, SecretBytes},
+    vault::Vault,
+};
+
+/// Overrides agent socket location
+const SOCKET_ENV: &str = "OTP_CLI_AGENT_SOCK";
+
+/// How often expired keys are wiped
+const PURGE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Connections that don't finish request or read response in time are dropped
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests and responses are small, anything larger is malformed
+const MAX_MESSAGE_LENGTH: u64 = 64 * 1024;
+
+type Keys = Arc<Mutex<HashMap<PathBuf, CachedKey>>>;
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// Asks for key of database with given path and master key salt
+    Get { path: PathBuf, salt: Vec<u8> },
+    Store {
+        path: PathBuf,
+        salt: Vec<u8>,
+        key: SecretBytes,
+        /// Agent default lifetime is used if not set
+        lifetime: Option<Duration>,
+    },
+    /// Forgets key of given database or all keys
+    Lock { path: Option<PathBuf> },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Key(SecretBytes),
+    NotFound,
+    Done,
+}
+
+struct CachedKey {
+    salt: Vec<u8>,
+    key: SecretBytes,
+    expires_at: Instant,
+}
+
+/// Agent socket path: `$OTP_CLI_AGENT_SOCK`, or `otp-cli/agent.sock` in `$XDG_RUNTIME_DIR`, or
+/// per-user directory in `/tmp`. Directory must be private to current user, see `check_socket_dir`
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+
+    let runtime_dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("otp-cli"),
+        None => env::temp_dir().join(format!("otp-cli-{}", current_uid())),
+    };
+
+    runtime_dir.join("agent.sock")
+}
+
+/// Runs agent in foreground, master keys are kept in memory until their lifetime expires
+pub fn serve(default_lifetime: Duration) -> Result<()> {
+    let socket_path = socket_path();
+
+    if let Some(dir) = socket_path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        // Directory in shared location may have been created by other user beforehand
+        check_socket_dir(&socket_path)?;
+    }
+
+    if socket_path.exists() {
+        if UnixStream::connect(&socket_path).is_ok() {
+            Err(Error::Agent(format!(
+                "Agent is already running at {}",
+                socket_path.display()
+            )))?;
+        }
+        // Left by agent that was killed
+        fs::remove_file(&socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&socket_path)?;
+    fs::set_permissions(&socket_path, fs::Permissions::from_mode(0o600))?;
+
+    println!("Agent is listening on {}", socket_path.display());
+
+    let keys: Keys = Arc::new(Mutex::new(HashMap::new()));
+
+    let purged_keys = keys.clone();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(PURGE_INTERVAL);
+            let now = Instant::now();
+            purged_keys
+                .lock()
+                .unwrap()
+                .retain(|_, cached| cached.expires_at > now);
+        }
+    });
+
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+
+        // Slow client must not block others, each connection gets own thread and timeouts
+        let keys = keys.clone();
+        thread::spawn(move || handle_connection(stream, &keys, default_lifetime).ok());
+    }
+
+    Ok(())
+}
+
+/// Serves single request, connections from other users are dropped without response
+fn handle_connection(
+    mut stream: UnixStream,
+    keys: &Keys,
+    default_lifetime: Duration,
+) -> Result<()> {
+    set_timeouts(&stream)?;
+    check_peer(&stream)?;
+
+    let request = read_message::<Request>(&mut stream)?;
+
+    let mut keys = keys.lock().unwrap();
+    let now = Instant::now();
+    keys.retain(|_, cached| cached.expires_at > now);
+
+    let response = match request {
+        Request::Get { path, salt } => match keys.get(&path) {
+            Some(cached) if cached.salt == salt => Response::Key(cached.key.clone()),
+            _ => Response::NotFound,
+        },
+        Request::Store {
+            path,
+            salt,
+            key,
+            lifetime,
+        } => {
+            keys.insert(
+                path,
+                CachedKey {
+                    salt,
+                    key,
+                    expires_at: now + lifetime.unwrap_or(default_lifetime),
+                },
+            );
+            Response::Done
+        }
+        Request::Lock { path: Some(path) } => {
+            keys.remove(&path);
+            Response::Done
+        }
+        Request::Lock { path: None } => {
+            keys.clear();
+            Response::Done
+        }
+    };
+    drop(keys);
+
+    write_message(&mut stream, &response)
+}
+
+/// Opens database with key cached by agent, falls back to asking for credentials if agent is not
+/// running or does not know the key
+pub fn open_vault(path: &Path) -> Result<Vault> {
+    match cached_vault(path) {
+        Some(vault) => Ok(vault),
+        None => Ok(Vault::open(path, ask_credentials)?),
+    }
+}
+
+/// Opens database with key cached by agent, if agent is running and knows the key
+pub fn cached_vault(path: &Path) -> Option<Vault> {
+    let key_file = KeyFile::load(path).ok()?;
+    let key = cached_key(path, &key_file.master_key_salt)?;
+    Vault::open_with_key(path, key_file, key).ok()
+}
+
+/// Same as `open_vault`, but creates database if it does not exist yet
+pub fn open_or_create_vault(path: &Path) -> Result<Vault> {
+    match path.exists() {
+        true => open_vault(path),
+        false => Ok(Vault::open_or_create(path, ask_credentials)?),
+    }
+}
+
+/// Hands master key of unlocked database to agent
+pub fn store_key(vault: &Vault, path: &Path, lifetime: Option<Duration>) -> Result<()> {
+    let request = Request::Store {
+        path: fs::canonicalize(path)?,
+        salt: vault.key_file.master_key_salt.clone(),
+        key: vault.encryption_key()?.clone(),
+        lifetime,
+    };
+
+    match send(&request)? {
+        Response::Done => Ok(()),
+        _ => Err(Error::Agent("Unexpected response from agent".to_owned()))?,
+    }
+}
+
+/// Makes agent forget key of given database or all keys
+pub fn forget_keys(path: Option<&Path>) -> Result<()> {
+    let request = Request::Lock {
+        path: path.map(fs::canonicalize).transpose()?,
+    };
+
+    match send(&request)? {
+        Response::Done => Ok(()),
+        _ => Err(Error::Agent("Unexpected response from agent".to_owned()))?,
+    }
+}
+
+fn cached_key(path: &Path, salt: &[u8]) -> Option<SecretBytes> {
+    let request = Request::Get {
+        path: fs::canonicalize(path).ok()?,
+        salt: salt.to_vec(),
+    };
+
+    match send(&request).ok()? {
+        Response::Key(key) => Some(key),
+        _ => None,
+    }
+}
+
+fn send(request: &Request) -> Result<Response> {
+    let socket_path = socket_path();
+    check_socket(&socket_path)?;
+    check_socket_dir(&socket_path)?;
+
+    let mut stream = UnixStream::connect(&socket_path).map_err(|err| {
+        Error::Agent(format!(
+            "Failed to connect to agent at {}: {}, is it running?",
+            socket_path.display(),
+            err
+        ))
+    })?;
+    set_timeouts(&stream)?;
+    check_peer(&stream)?;
+
+    write_message(&mut stream, request)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    read_message(&mut stream)
+}
+
+/// Messages carry keys, so buffers are wiped after use
+fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<()> {
+    let serialized = Zeroizing::new(serde_json::to_vec(message)?);
+    stream.write_all(&serialized)?;
+    Ok(())
+}
+
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &mut UnixStream) -> Result<T> {
+    let mut serialized = Zeroizing::new(Vec::new());
+    stream
+        .take(MAX_MESSAGE_LENGTH)
+        .read_to_end(&mut serialized)?;
+    Ok(serde_json::from_slice(&serialized)?)
+}
+
+fn current_uid() -> libc::uid_t {
+    unsafe { libc::getuid() }
+}
+
+/// Socket directory must be owned by current user and inaccessible to others, otherwise other
+/// user could replace socket with own one and collect keys
+fn check_socket_dir(socket_path: &Path) -> Result<()> {
+    let Some(dir) = socket_path.parent() else {
+        return Ok(());
+    };
+    let metadata = fs::symlink_metadata(dir)?;
+
+    if !metadata.is_dir() || metadata.uid() != current_uid() || metadata.mode() & 0o777 != 0o700 {
+        Err(Error::Agent(format!(
+            "Agent socket directory {} must be owned by current user and have mode 0700",
+            dir.display()
+        )))?;
+    }
+
+    Ok(())
+}
+
+fn check_socket(socket_path: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(socket_path).map_err(|err| {
+        Error::Agent(format!(
+            "Failed to connect to agent at {}: {}, is it running?",
+            socket_path.display(),
+            err
+        ))
+    })?;
+
+    if !metadata.file_type().is_socket() || metadata.uid() != current_uid() {
+        Err(Error::Agent(format!(
+            "Agent socket {} is not owned by current user",
+            socket_path.display()
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// Both agent and clients only talk to processes of the same user
+fn check_peer(stream: &UnixStream) -> Result<()> {
+    if peer_uid(stream)? != current_uid() {
+        Err(Error::Agent("Agent peer belongs to other user".to_owned()))?;
+    }
+
+    Ok(())
+}
+
+fn set_timeouts(stream: &UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = size_of::<libc::ucred>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&raw mut credentials).cast(),
+            &mut length,
+        )
+    };
+
+    match result {
+        0 => Ok(credentials.uid),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid = 0;
+    let mut gid = 0;
+
+    match unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } {
+        0 => Ok(uid),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_dir_must_be_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("agent.sock");
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+
+        let private = check_socket_dir(&socket_path);
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        let shared = check_socket_dir(&socket_path);
+
+        assert!(private.is_ok());
+        assert!(shared.is_err());
+    }
+
+    #[test]
+    fn peer_is_current_user() {
+        let (client, agent) = UnixStream::pair().unwrap();
+
+        assert_eq!(peer_uid(&client).unwrap(), current_uid());
+        assert!(check_peer(&agent).is_ok());
+    }
+}
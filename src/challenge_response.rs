@@ -0,0 +1,99 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use zeroize::Zeroizing;
+
+use crate::{
+    error::{Error, Result},
+    types::{ChallengeResponse, SecretBytes},
+};
+
+/// Helper command from command line or config file
+static HELPER_COMMAND: OnceLock<String> = OnceLock::new();
+
+/// Sets helper command used for databases that require challenge-response, database itself
+/// never supplies it since it is not authenticated before unlocking
+pub fn set_helper_command(command: String) {
+    HELPER_COMMAND.set(command).ok();
+}
+
+/// Runs configured helper command on challenge of database
+pub fn respond(challenge_response: &ChallengeResponse) -> Result<SecretBytes> {
+    let command = HELPER_COMMAND.get().ok_or_else(|| {
+        Error::CredentialsRequired(
+            "Database requires challenge-response, set helper command with --challenge-command or challenge_command in config file".to_owned(),
+        )
+    })?;
+
+    run_helper(command, &challenge_response.challenge)
+}
+
+/// Runs helper command passing hex encoded challenge to its stdin, returns what it printed to
+/// stdout. Stderr is left attached to terminal so helper can ask user to touch the device
+pub fn run_helper(command: &str, challenge: &[u8]) -> Result<SecretBytes> {
+    let mut helper = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::ChallengeResponse(err.to_string()))?;
+
+    if let Some(mut stdin) = helper.stdin.take() {
+        writeln!(stdin, "{}", hex::encode(challenge))?;
+    }
+
+    let output = helper.wait_with_output()?;
+    let stdout = Zeroizing::new(output.stdout);
+
+    if !output.status.success() {
+        Err(Error::ChallengeResponse(output.status.to_string()))?;
+    }
+
+    let response = stdout.trim_ascii();
+    if response.is_empty() {
+        Err(Error::ChallengeResponse("empty response".to_owned()))?;
+    }
+
+    Ok(SecretBytes::from(response.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::{NamedTempFile, TempPath};
+
+    /// Writes executable stub helper to temp file, file is closed so it can be executed and
+    /// removed when returned path is dropped
+    fn stub(script: &str) -> TempPath {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "#!/bin/sh\n{}\n", script).unwrap();
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o700)).unwrap();
+        file.into_temp_path()
+    }
+
+    #[test]
+    fn stub_helper() {
+        let path = stub("read challenge\necho \"response-$challenge\"");
+        let response = run_helper(&path.to_string_lossy(), &[0xab, 0x01]).unwrap();
+
+        assert_eq!(&*response, b"response-ab01");
+    }
+
+    #[test]
+    fn failed_helper() {
+        let failing = stub("exit 3");
+        let silent = stub("read challenge");
+        let failed = run_helper(&failing.to_string_lossy(), b"challenge").unwrap_err();
+        let empty = run_helper(&silent.to_string_lossy(), b"challenge").unwrap_err();
+
+        assert!(matches!(failed, Error::ChallengeResponse(_)));
+        assert_eq!(
+            empty.to_string(),
+            "Challenge-response command failed: empty response"
+        );
+    }
+}
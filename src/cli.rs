@@ -1,14 +1,38 @@
-use anyhow::{Result, anyhow};
-use clap::{CommandFactory, Parser, Subcommand};
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::ArgValueCandidates;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use crate::{commands::*, types::BackupType};
+use crate::{
+    challenge_response,
+    clock::DEFAULT_SERVER,
+    commands::*,
+    completions::{Shell, profile_token_names, token_names},
+    config::Config,
+    error::Error,
+    prompt,
+    types::{KeyFile, SecretString, Token, TokenAlgorithm, TokenType},
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
-    /// Path to database with encrypted tokens (required for all commands except `parse`)
+    /// Path to database with encrypted tokens (required for all commands except `parse`, default database from config file is used if omitted)
     keyfile: Option<PathBuf>,
+    /// Use database of this profile from config file (may be repeated for `list`, `code` and `start`, key file takes precedence if specified)
+    #[arg(short, long = "profile", global = true, value_name = "PROFILE")]
+    profiles: Vec<String>,
+    /// Open databases of all profiles from config file (for `list`, `code` and `start`)
+    #[arg(long, global = true)]
+    all_profiles: bool,
+    /// Helper command for databases that require challenge-response, it reads hex encoded challenge from stdin and prints response to stdout (overrides config file)
+    #[arg(long, global = true, value_name = "COMMAND")]
+    challenge_command: Option<String>,
+    /// Key file for databases that require it, asked if neither this nor config file sets it
+    #[arg(long, global = true, value_name = "FILE")]
+    unlock_key_file: Option<PathBuf>,
     /// Command
     #[command(subcommand)]
     command: Option<Commands>,
@@ -24,6 +48,9 @@ enum Commands {
         /// Path to backup file
         #[arg(short, long, value_name = "FILE")]
         file: PathBuf,
+        /// Skip invalid lines of tokens list or Google Authenticator backup and report them
+        #[arg(long)]
+        skip_invalid: bool,
     },
     /// Convert backup file to own file format (if existing database specified, tokens will be appended)
     Convert {
@@ -33,53 +60,674 @@ enum Commands {
         /// Path to backup file
         #[arg(short, long, value_name = "FILE")]
         input: PathBuf,
+        /// Template for token names, supports {issuer}, {label} and {type} placeholders
+        #[arg(
+            short,
+            long,
+            value_name = "TEMPLATE",
+            default_value = "{issuer}-{label}"
+        )]
+        name_template: String,
+        /// What to do when token with the same name already exists in database
+        #[arg(short = 'c', long, value_name = "STRATEGY", default_value = "suffix")]
+        on_conflict: ConflictStrategy,
+        /// What to do when token with the same secret and parameters already exists in database
+        #[arg(short = 'd', long, value_name = "STRATEGY", default_value = "skip")]
+        on_duplicate: DuplicateStrategy,
+        /// Show what would be added, renamed, overwritten or skipped without saving database, nothing is asked
+        #[arg(long)]
+        dry_run: bool,
+        /// Pick tokens to import and change their names in checklist before import
+        #[arg(short, long)]
+        select: bool,
+        /// Import valid lines of tokens list or Google Authenticator backup, report invalid ones
+        #[arg(long)]
+        skip_invalid: bool,
     },
-    /// Add token to database from otpauth url
+    /// Add token to database from otpauth url or raw secret (asked interactively if neither is specified)
     Add {
         /// Token name
         #[arg(short = 'n', long, value_name = "NAME")]
         name: String,
-        /// Token otpauth url
-        #[arg(short = 'u', long, value_name = "OTPAUTH URL")]
-        url: String,
+        /// Token otpauth url, token parameters are taken from it
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "OTPAUTH URL",
+            conflicts_with_all = [
+                "secret", "encoding", "issuer", "label", "digits", "period", "algorithm",
+                "token_type", "counter"
+            ]
+        )]
+        url: Option<String>,
+        /// Token secret (note that it will be saved in shell history, omit it to enter secret interactively)
+        #[arg(short = 's', long, value_name = "SECRET")]
+        secret: Option<String>,
+        /// Secret encoding
+        #[arg(short = 'e', long, value_name = "ENCODING", default_value = "base32")]
+        encoding: SecretEncoding,
+        /// Token issuer
+        #[arg(long, default_value = "")]
+        issuer: String,
+        /// Token label (account name)
+        #[arg(long, default_value = "")]
+        label: String,
+        /// Amount of digits in code
+        #[arg(long, default_value_t = 6)]
+        digits: u32,
+        /// Code validity period in seconds
+        #[arg(long, default_value_t = 30)]
+        period: u64,
+        /// Hashing algorithm
+        #[arg(long, default_value = "sha1")]
+        algorithm: AlgorithmArg,
+        /// Token type
+        #[arg(
+            short = 't',
+            long = "type",
+            value_name = "TYPE",
+            default_value = "totp"
+        )]
+        token_type: TokenTypeArg,
+        /// Initial counter value (for HOTP tokens)
+        #[arg(long, default_value_t = 0)]
+        counter: u32,
+        /// Overwrite token if token with the same name already exists
+        #[arg(short, long)]
+        force: bool,
     },
     /// Use token from specified database
     Use {
         /// Token name
-        #[arg(short, long, value_name = "TOKEN")]
-        token: String,
+        #[arg(
+            short,
+            long,
+            value_name = "TOKEN",
+            required_unless_present = "tag",
+            add = ArgValueCandidates::new(token_names)
+        )]
+        token: Option<String>,
+        /// Use all tokens with this tag
+        #[arg(long, value_name = "TAG", conflicts_with = "token")]
+        tag: Option<String>,
+        /// Copy next code instead of current one when it is about to expire (overrides config file)
+        #[arg(long, conflicts_with = "no_copy_next")]
+        copy_next: bool,
+        /// Copy current code even if it is about to expire (overrides config file)
+        #[arg(long)]
+        no_copy_next: bool,
+    },
+    /// Print current code of token from specified database and exit
+    Code {
+        /// Token name
+        #[arg(
+            short,
+            long,
+            value_name = "TOKEN",
+            required_unless_present = "tag",
+            add = ArgValueCandidates::new(token_names)
+        )]
+        token: Option<String>,
+        /// Print codes of all tokens with this tag
+        #[arg(long, value_name = "TAG", conflicts_with = "token")]
+        tag: Option<String>,
+        /// Print code valid at given time (Unix time or RFC 3339 date, UTC if offset is not specified) instead of current one
+        #[arg(long, value_name = "TIME", value_parser = parse_time)]
+        at: Option<u64>,
+        /// Print code given amount of time steps (HOTP counter values) after current one, negative for past codes
+        #[arg(long, value_name = "STEPS", allow_negative_numbers = true)]
+        offset: Option<i64>,
+    },
+    /// Check whether code is valid for token and at which time step offset (or HOTP counter)
+    Verify {
+        /// Token name
+        #[arg(add = ArgValueCandidates::new(token_names))]
+        name: String,
+        /// Code to check
+        code: String,
+        /// Amount of time steps (or HOTP counter values) before and after current one to check
+        #[arg(short, long, default_value_t = 1)]
+        window: u32,
     },
     /// Starts app in interactive mode
-    Start,
+    Start {
+        /// Compare system clock with SNTP server on start (overrides config file)
+        #[arg(long, conflicts_with = "no_clock_check")]
+        clock_check: bool,
+        /// Don't compare system clock with SNTP server on start (overrides config file)
+        #[arg(long)]
+        no_clock_check: bool,
+        /// Copy next code instead of current one when it is about to expire (overrides config file)
+        #[arg(long, conflicts_with = "no_copy_next")]
+        copy_next: bool,
+        /// Copy current code even if it is about to expire (overrides config file)
+        #[arg(long)]
+        no_copy_next: bool,
+    },
+    /// Compare system clock with SNTP server or reference time, fails if drift is large enough to break time based codes
+    ClockCheck {
+        /// Reference time (Unix time or RFC 3339 date, UTC if offset is not specified), server is not asked if specified
+        #[arg(short, long, value_name = "TIME", value_parser = parse_time)]
+        reference: Option<u64>,
+        /// SNTP server address
+        #[arg(short, long, value_name = "HOST:PORT", default_value = DEFAULT_SERVER)]
+        server: String,
+    },
+    /// List tokens stored in database (does not require password)
+    List,
+    /// Copy token to database of other profile
+    Copy {
+        /// Token to copy, as profile/name
+        #[arg(value_name = "PROFILE/NAME", add = ArgValueCandidates::new(profile_token_names))]
+        source: String,
+        /// Destination profile, token name may be changed with profile/name
+        #[arg(value_name = "PROFILE[/NAME]")]
+        destination: String,
+        /// Overwrite token if token with the same name already exists in destination database
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Move token to database of other profile
+    Move {
+        /// Token to move, as profile/name
+        #[arg(value_name = "PROFILE/NAME", add = ArgValueCandidates::new(profile_token_names))]
+        source: String,
+        /// Destination profile, token name may be changed with profile/name
+        #[arg(value_name = "PROFILE[/NAME]")]
+        destination: String,
+        /// Overwrite token if token with the same name already exists in destination database
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Rename token
+    Rename {
+        /// Current token name
+        #[arg(add = ArgValueCandidates::new(token_names))]
+        old_name: String,
+        /// New token name
+        new_name: String,
+    },
+    /// Remove token from database
+    Remove {
+        /// Token name
+        #[arg(add = ArgValueCandidates::new(token_names))]
+        name: String,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Show token parameters
+    Show {
+        /// Token name
+        #[arg(add = ArgValueCandidates::new(token_names))]
+        name: String,
+        /// Also print token secret (requires password)
+        #[arg(short, long)]
+        secret: bool,
+    },
+    /// Find tokens with the same secret and parameters
+    Dedupe {
+        /// Remove duplicates keeping first token (in name order) of every group
+        #[arg(short, long)]
+        remove: bool,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Change token parameters
+    Edit {
+        /// Token name
+        #[arg(add = ArgValueCandidates::new(token_names))]
+        name: String,
+        /// New issuer
+        #[arg(long)]
+        issuer: Option<String>,
+        /// New label (account name)
+        #[arg(long)]
+        label: Option<String>,
+        /// New amount of digits in code (not for Steam, mOTP and Yandex tokens)
+        #[arg(long)]
+        digits: Option<u32>,
+        /// New code validity period in seconds (not for Steam, mOTP and Yandex tokens)
+        #[arg(long)]
+        period: Option<u64>,
+        /// New hashing algorithm
+        #[arg(long)]
+        algorithm: Option<AlgorithmArg>,
+        /// Move token to group (empty value removes token from group)
+        #[arg(long)]
+        group: Option<String>,
+        /// Add tag to token (may be repeated)
+        #[arg(long = "add-tag", value_name = "TAG")]
+        add_tags: Vec<String>,
+        /// Remove tag from token (may be repeated)
+        #[arg(long = "remove-tag", value_name = "TAG")]
+        remove_tags: Vec<String>,
+        /// Mark or unmark token as favorite
+        #[arg(long)]
+        favorite: Option<bool>,
+        /// New notes
+        #[arg(long)]
+        notes: Option<String>,
+        /// Position in token lists (lower goes first)
+        #[arg(
+            long,
+            allow_negative_numbers = true,
+            conflicts_with = "clear_sort_order"
+        )]
+        sort_order: Option<i64>,
+        /// Remove custom position in token lists
+        #[arg(long)]
+        clear_sort_order: bool,
+    },
+    /// Encrypt token names and metadata along with token keys, database becomes unreadable without password
+    Seal,
+    /// Store token names and metadata in plain text again, only token keys stay encrypted
+    Unseal,
+    /// Change database password and additional unlock factors (factors that are not specified are removed)
+    Rekey {
+        /// Require key file to unlock database, file with random contents is created if it does not exist
+        #[arg(short, long, value_name = "FILE")]
+        key_file: Option<PathBuf>,
+        /// Require response of challenge-response helper (set with --challenge-command or in config file) to unlock database
+        #[arg(short = 'c', long)]
+        challenge_response: bool,
+    },
+    /// Run agent that keeps unlocked databases in memory, so `use`, `code`, `add` and `convert` don't ask for password
+    Agent {
+        /// How long database stays unlocked (e.g. 15m, 1h 30m)
+        #[arg(short, long, value_name = "DURATION", default_value = "15m", value_parser = humantime::parse_duration)]
+        lifetime: Duration,
+    },
+    /// Unlock database in running agent
+    Unlock {
+        /// How long database stays unlocked, agent default is used if not specified
+        #[arg(short, long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+        lifetime: Option<Duration>,
+    },
+    /// Lock database in running agent (all databases if key file is not specified)
+    Lock,
+    /// Print shell completion script, completes token names without asking for password
+    Completions {
+        /// Shell to generate script for
+        shell: Shell,
+    },
+    /// Print man page of command (or of otp-cli itself), or write pages of all commands to directory
+    Man {
+        /// Command to print page of
+        #[arg(value_name = "COMMAND", conflicts_with = "out_dir")]
+        command: Option<String>,
+        /// Write pages of otp-cli and all its commands to this directory
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum BackupType {
+    TokensList,
+    FreeOtp,
+    GoogleAuth,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ConflictStrategy {
+    /// Keep existing token, imported one is skipped
+    Skip,
+    /// Replace existing token with imported one
+    Overwrite,
+    /// Add imported token under name with numeric suffix
+    Suffix,
+    /// Ask what to do for every conflicting token
+    Prompt,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DuplicateStrategy {
+    /// Do not import token that duplicates existing one
+    Skip,
+    /// Fill in missing issuer, label and counter of existing token from imported one
+    Merge,
+    /// Import token anyway
+    Keep,
+}
+
+/// Which tokens command operates on
+pub enum TokenSelector {
+    /// Single token with this name
+    Name(String),
+    /// All tokens with this tag
+    Tag(String),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum SecretEncoding {
+    Base32,
+    Hex,
+}
+
+/// Hashing algorithm argument, mirrors [`TokenAlgorithm`]
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum AlgorithmArg {
+    Sha1,
+    Sha256,
+    Sha512,
+    /// HMAC-MD5, used by some legacy tokens
+    Md5,
+}
+
+impl From<AlgorithmArg> for TokenAlgorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::Sha1 => TokenAlgorithm::Sha1,
+            AlgorithmArg::Sha256 => TokenAlgorithm::Sha256,
+            AlgorithmArg::Sha512 => TokenAlgorithm::Sha512,
+            AlgorithmArg::Md5 => TokenAlgorithm::Md5,
+        }
+    }
+}
+
+/// Token type argument, mirrors [`TokenType`]
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum TokenTypeArg {
+    /// Counter based (RFC 4226)
+    Hotp,
+    /// Time based (RFC 6238)
+    Totp,
+    /// Steam Guard, time based with 5 character codes from Steam alphabet
+    Steam,
+    /// Mobile-OTP, MD5 of time, secret and PIN, new 6 hex digits code every 10 seconds
+    Motp,
+    /// Yandex Key, HMAC-SHA256 keyed with PIN and secret, 8 letter codes
+    Yandex,
+}
+
+impl From<TokenTypeArg> for TokenType {
+    fn from(token_type: TokenTypeArg) -> Self {
+        match token_type {
+            TokenTypeArg::Hotp => TokenType::Hotp,
+            TokenTypeArg::Totp => TokenType::Totp,
+            TokenTypeArg::Steam => TokenType::Steam,
+            TokenTypeArg::Motp => TokenType::Motp,
+            TokenTypeArg::Yandex => TokenType::Yandex,
+        }
+    }
+}
+
+impl TokenSelector {
+    /// Tokens matching selector in display order, fails if nothing matches
+    pub fn select<'a>(&self, key_file: &'a KeyFile) -> Result<Vec<(&'a String, &'a Token)>, Error> {
+        match self {
+            TokenSelector::Name(name) => key_file
+                .tokens
+                .get_key_value(name)
+                .map(|token| vec![token])
+                .ok_or_else(|| Error::TokenNotFound(name.to_owned())),
+            TokenSelector::Tag(tag) => key_file.tagged_tokens(tag),
+        }
+    }
 }
 
 impl Cli {
     pub fn run() -> Result<()> {
         let cli = Cli::parse();
 
-        let maybe_keyfile = cli.keyfile.as_ref();
+        // Commands that don't open databases work even if config file is malformed
+        let uses_config = !matches!(
+            cli.command,
+            None | Some(
+                Commands::Parse { .. }
+                    | Commands::ClockCheck { .. }
+                    | Commands::Agent { .. }
+                    | Commands::Completions { .. }
+                    | Commands::Man { .. }
+            )
+        );
+        let mut config = match uses_config {
+            true => Config::load()?,
+            false => Config::default(),
+        };
+        if let Some(command) = cli
+            .challenge_command
+            .clone()
+            .or(config.unlock.challenge_command.clone())
+        {
+            challenge_response::set_helper_command(command);
+        }
+        if let Some(path) = cli.unlock_key_file.clone().or(config.unlock_key_file()) {
+            prompt::set_key_file(path);
+        }
+        let databases = match uses_config {
+            true => config.databases(cli.keyfile.as_deref(), &cli.profiles, cli.all_profiles)?,
+            false => Vec::new(),
+        };
+        let keyfile = |command: &str| match databases.as_slice() {
+            [database] => Ok(database.path.as_path()),
+            [] => Err(Error::InvalidArgument(format!(
+                "Key file must be specified for `{}` command",
+                command
+            ))),
+            _ => Err(Error::InvalidArgument(format!(
+                "`{}` command works with single database",
+                command
+            ))),
+        };
 
         match &cli.command {
-            Some(Commands::Parse { backup_type, file }) => parse_backup_file(backup_type, file),
-            Some(Commands::Convert { backup_type, input }) => {
-                let keyfile = maybe_keyfile
-                    .ok_or(anyhow!("Key file must be specified for `convert` command"))?;
-                convert_backup_file(backup_type, input, keyfile)
-            }
-            Some(Commands::Add { name, url }) => {
-                let keyfile =
-                    maybe_keyfile.ok_or(anyhow!("Key file must be specified for `add` command"))?;
-                add_token(keyfile, name.to_owned(), url)
-            }
-            Some(Commands::Use { token }) => {
-                let keyfile =
-                    maybe_keyfile.ok_or(anyhow!("Key file must be specified for `use` command"))?;
-                use_token(keyfile, token.as_str())
-            }
-            Some(Commands::Start) => {
-                let keyfile = maybe_keyfile
-                    .ok_or(anyhow!("Key file must be specified for `start` command"))?;
-                start_interactive(keyfile)
+            Some(Commands::Parse {
+                backup_type,
+                file,
+                skip_invalid,
+            }) => parse_backup_file(backup_type, file, *skip_invalid),
+            Some(Commands::Convert {
+                backup_type,
+                input,
+                name_template,
+                on_conflict,
+                on_duplicate,
+                dry_run,
+                select,
+                skip_invalid,
+            }) => {
+                let keyfile = keyfile("convert")?;
+                convert_backup_file(
+                    backup_type,
+                    input,
+                    keyfile,
+                    &ImportOptions {
+                        name_template: name_template.clone(),
+                        on_conflict: *on_conflict,
+                        on_duplicate: *on_duplicate,
+                        dry_run: *dry_run,
+                        select: *select,
+                        skip_invalid: *skip_invalid,
+                    },
+                )
+            }
+            Some(Commands::Add {
+                name,
+                url,
+                secret,
+                encoding,
+                issuer,
+                label,
+                digits,
+                period,
+                algorithm,
+                token_type,
+                counter,
+                force,
+            }) => {
+                let keyfile = keyfile("add")?;
+                let parameters = TokenParameters {
+                    issuer: issuer.clone(),
+                    label: label.clone(),
+                    digits: *digits,
+                    period: *period,
+                    algorithm: (*algorithm).into(),
+                    token_type: (*token_type).into(),
+                    counter: *counter,
+                };
+                let source = match (url, secret) {
+                    (Some(url), _) => TokenSource::Url(url.clone()),
+                    (None, Some(secret)) => TokenSource::Secret {
+                        secret: SecretString::from(secret.clone()),
+                        encoding: *encoding,
+                        parameters,
+                    },
+                    (None, None) => TokenSource::Prompt {
+                        encoding: *encoding,
+                        parameters,
+                    },
+                };
+                add_token(keyfile, name.to_owned(), source, *force)
+            }
+            Some(Commands::Use {
+                token,
+                tag,
+                copy_next,
+                no_copy_next,
+            }) => {
+                let keyfile = keyfile("use")?;
+                config.clipboard.copy_next =
+                    (config.clipboard.copy_next || *copy_next) && !*no_copy_next;
+                use_token(keyfile, &token_selector(token, tag), &config)
+            }
+            Some(Commands::Code {
+                token,
+                tag,
+                at,
+                offset,
+            }) => {
+                if databases.is_empty() {
+                    Err(Error::InvalidArgument(
+                        "Key file must be specified for `code` command".to_owned(),
+                    ))?;
+                }
+                print_code(&databases, &token_selector(token, tag), *at, *offset)
+            }
+            Some(Commands::Verify { name, code, window }) => {
+                let keyfile = keyfile("verify")?;
+                verify_token(keyfile, name, code, *window)
+            }
+            Some(Commands::Start {
+                clock_check,
+                no_clock_check,
+                copy_next,
+                no_copy_next,
+            }) => {
+                if databases.is_empty() {
+                    Err(Error::InvalidArgument(
+                        "Key file must be specified for `start` command".to_owned(),
+                    ))?;
+                }
+                config.clipboard.copy_next =
+                    (config.clipboard.copy_next || *copy_next) && !*no_copy_next;
+                config.interface.clock_check =
+                    (config.interface.clock_check || *clock_check) && !*no_clock_check;
+                start_interactive(&databases, &config)
+            }
+            Some(Commands::ClockCheck { reference, server }) => check_clock(*reference, server),
+            Some(Commands::List) => {
+                if databases.is_empty() {
+                    Err(Error::InvalidArgument(
+                        "Key file must be specified for `list` command".to_owned(),
+                    ))?;
+                }
+                list_tokens(&databases)
+            }
+            Some(Commands::Copy {
+                source,
+                destination,
+                force,
+            }) => copy_token(&config, source, destination, *force, false),
+            Some(Commands::Move {
+                source,
+                destination,
+                force,
+            }) => copy_token(&config, source, destination, *force, true),
+            Some(Commands::Rename { old_name, new_name }) => {
+                let keyfile = keyfile("rename")?;
+                rename_token(keyfile, old_name, new_name)
+            }
+            Some(Commands::Remove { name, yes }) => {
+                let keyfile = keyfile("remove")?;
+                remove_token(keyfile, name, *yes)
+            }
+            Some(Commands::Show { name, secret }) => {
+                let keyfile = keyfile("show")?;
+                show_token(keyfile, name, *secret)
+            }
+            Some(Commands::Dedupe { remove, yes }) => {
+                let keyfile = keyfile("dedupe")?;
+                dedupe_tokens(keyfile, *remove, *yes)
+            }
+            Some(Commands::Edit {
+                name,
+                issuer,
+                label,
+                digits,
+                period,
+                algorithm,
+                group,
+                add_tags,
+                remove_tags,
+                favorite,
+                notes,
+                sort_order,
+                clear_sort_order,
+            }) => {
+                let keyfile = keyfile("edit")?;
+                edit_token(
+                    keyfile,
+                    name,
+                    TokenChanges {
+                        issuer: issuer.clone(),
+                        label: label.clone(),
+                        digits: *digits,
+                        period: *period,
+                        algorithm: algorithm.map(Into::into),
+                        group: group.clone(),
+                        add_tags: add_tags.clone(),
+                        remove_tags: remove_tags.clone(),
+                        favorite: *favorite,
+                        notes: notes.clone(),
+                        sort_order: match clear_sort_order {
+                            true => Some(None),
+                            false => sort_order.map(Some),
+                        },
+                    },
+                )
+            }
+            Some(Commands::Seal) => {
+                let keyfile = keyfile("seal")?;
+                seal_database(keyfile, true)
+            }
+            Some(Commands::Unseal) => {
+                let keyfile = keyfile("unseal")?;
+                seal_database(keyfile, false)
+            }
+            Some(Commands::Rekey {
+                key_file,
+                challenge_response,
+            }) => {
+                let keyfile = keyfile("rekey")?;
+                rekey_database(keyfile, key_file.as_deref(), *challenge_response)
+            }
+            Some(Commands::Agent { lifetime }) => run_agent(*lifetime),
+            Some(Commands::Unlock { lifetime }) => {
+                let keyfile = keyfile("unlock")?;
+                unlock_database(keyfile, *lifetime)
+            }
+            Some(Commands::Lock) => match databases.as_slice() {
+                [] => lock_database(None),
+                databases => databases
+                    .iter()
+                    .try_for_each(|database| lock_database(Some(&database.path))),
+            },
+            Some(Commands::Completions { shell }) => print_completions(*shell),
+            Some(Commands::Man { command, out_dir }) => {
+                print_man(command.as_deref(), out_dir.as_deref())
             }
             None => {
                 Cli::command().print_help().ok();
@@ -88,3 +736,58 @@ impl Cli {
         }
     }
 }
+
+/// Parses Unix time or RFC 3339 date (`2024-01-31T12:00:00+02:00`), date without offset is
+/// taken as UTC
+fn parse_time(value: &str) -> Result<u64, String> {
+    if let Ok(seconds) = value.parse() {
+        return Ok(seconds);
+    }
+
+    let seconds = match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => time.timestamp(),
+        Err(_) => NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+            .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+            .map_err(|err| err.to_string())?
+            .and_utc()
+            .timestamp(),
+    };
+
+    u64::try_from(seconds).map_err(|_| "time before 1970 is not supported".to_owned())
+}
+
+/// Builds selector from mutually exclusive `--token` and `--tag` arguments
+fn token_selector(token: &Option<String>, tag: &Option<String>) -> TokenSelector {
+    match (token, tag) {
+        (Some(token), _) => TokenSelector::Name(token.clone()),
+        (None, Some(tag)) => TokenSelector::Tag(tag.clone()),
+        (None, None) => unreachable!("clap requires either token or tag"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("1706702400"), Ok(1_706_702_400));
+        assert_eq!(parse_time("2024-01-31T12:00:00Z"), Ok(1_706_702_400));
+        assert_eq!(parse_time("2024-01-31T14:00:00+02:00"), Ok(1_706_702_400));
+        assert_eq!(parse_time("2024-01-31T12:00:00"), Ok(1_706_702_400));
+        assert_eq!(parse_time("2024-01-31 12:00:00.5"), Ok(1_706_702_400));
+        assert!(parse_time("1969-12-31T23:59:59Z").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn url_conflicts_with_token_parameters() {
+        let url = "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP";
+        assert!(Cli::try_parse_from(["otp-cli", "add", "-n", "a", "-u", url]).is_ok());
+        for parameter in ["--issuer=x", "--digits=8", "--period=60", "--type=hotp"] {
+            assert!(
+                Cli::try_parse_from(["otp-cli", "add", "-n", "a", "-u", url, parameter]).is_err()
+            );
+        }
+    }
+}
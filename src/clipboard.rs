@@ -0,0 +1,26 @@
+use cli_clipboard::{ClipboardContext, ClipboardProvider};
+
+use crate::error::{Error, Result};
+
+/// System clipboard, failure to access it is reported on copy, so interactive commands work
+/// without clipboard
+pub struct Clipboard {
+    context: std::result::Result<ClipboardContext, String>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            context: ClipboardContext::new().map_err(|err| err.to_string()),
+        }
+    }
+
+    pub fn copy(&mut self, text: String) -> Result<()> {
+        match &mut self.context {
+            Ok(context) => context
+                .set_contents(text)
+                .map_err(|err| Error::Clipboard(err.to_string())),
+            Err(err) => Err(Error::Clipboard(err.clone())),
+        }
+    }
+}
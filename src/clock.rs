@@ -0,0 +1,126 @@
+use anyhow::Result;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
+/// Server asked when no reference time is given
+pub const DEFAULT_SERVER: &str = "pool.ntp.org:123";
+/// How long to wait for server response
+pub const TIMEOUT: Duration = Duration::from_secs(3);
+/// Drift in seconds after which codes often fall into neighbouring time step, half of the usual
+/// 30 second period
+pub const DRIFT_THRESHOLD: f64 = 15.0;
+
+/// Seconds between NTP epoch (1900) and Unix epoch (1970)
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+const PACKET_LENGTH: usize = 48;
+
+/// Asks SNTP server (RFC 4330) for time, returns drift of system clock in seconds (positive when
+/// system clock is ahead of server)
+pub fn query_server(server: &str, timeout: Duration) -> Result<f64> {
+    let address = server
+        .to_socket_addrs()
+        .map_err(|err| Error::Sntp(format!("Failed to resolve {}: {}", server, err)))?
+        .next()
+        .ok_or_else(|| Error::Sntp(format!("Failed to resolve {}", server)))?;
+    let socket = match address.is_ipv4() {
+        true => UdpSocket::bind(("0.0.0.0", 0))?,
+        false => UdpSocket::bind(("::", 0))?,
+    };
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(address)?;
+
+    let mut request = [0; PACKET_LENGTH];
+    // Leap indicator 0, version 4, client mode
+    request[0] = 0b00_100_011;
+
+    // Transmit timestamp of request comes back as originate timestamp of response, so reply to
+    // other request (or spoofed one) is detected
+    let originate = system_time()?;
+    request[40..48].copy_from_slice(&to_ntp_timestamp(originate));
+    socket.send(&request)?;
+    let mut response = [0; PACKET_LENGTH];
+    let length = socket
+        .recv(&mut response)
+        .map_err(|err| Error::Sntp(format!("No response from {}: {}", server, err)))?;
+    let destination = system_time()?;
+
+    // Server mode is expected, stratum 0 is "kiss-o'-death" response
+    if length < PACKET_LENGTH
+        || response[0] & 0b111 != 4
+        || response[1] == 0
+        || response[24..32] != request[40..48]
+    {
+        Err(Error::Sntp(format!(
+            "Invalid SNTP response from {}",
+            server
+        )))?;
+    }
+
+    let receive = ntp_timestamp(&response[32..40]);
+    let transmit = ntp_timestamp(&response[40..48]);
+    let server_offset = ((receive - originate) + (transmit - destination)) / 2.0;
+
+    Ok(-server_offset)
+}
+
+/// Drift of system clock in seconds from given Unix time
+pub fn reference_drift(reference: u64) -> Result<f64> {
+    Ok(system_time()? - reference as f64)
+}
+
+/// Warning shown when drift is large enough to break TOTP codes
+pub fn drift_warning(drift: f64) -> Option<String> {
+    (drift.abs() >= DRIFT_THRESHOLD).then(|| {
+        format!(
+            "System clock is {:.0} seconds {}, time based codes may be rejected",
+            drift.abs(),
+            if drift > 0.0 { "ahead" } else { "behind" }
+        )
+    })
+}
+
+fn system_time() -> Result<f64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64())
+}
+
+fn to_ntp_timestamp(time: f64) -> [u8; 8] {
+    let time = time + NTP_UNIX_OFFSET;
+    let seconds = time.trunc() as u32;
+    let fraction = (time.fract() * (1u64 << 32) as f64) as u32;
+
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+    bytes[4..].copy_from_slice(&fraction.to_be_bytes());
+    bytes
+}
+
+fn ntp_timestamp(bytes: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes(bytes[..4].try_into().unwrap());
+    let fraction = u32::from_be_bytes(bytes[4..].try_into().unwrap());
+    seconds as f64 - NTP_UNIX_OFFSET + fraction as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntp_timestamp_roundtrip() {
+        let time = 1_706_702_400.25;
+        let bytes = to_ntp_timestamp(time);
+
+        assert_eq!(&bytes[..4], &3_915_691_200u32.to_be_bytes());
+        assert_eq!(ntp_timestamp(&bytes), time);
+    }
+
+    #[test]
+    fn drift_warning_threshold() {
+        assert_eq!(drift_warning(14.9), None);
+        assert_eq!(
+            drift_warning(-20.0).unwrap(),
+            "System clock is 20 seconds behind, time based codes may be rejected"
+        );
+    }
+}
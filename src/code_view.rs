@@ -0,0 +1,146 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::otp::{self, Generator, Validity};
+
+/// Current code of token, along with next one when current code is about to expire
+pub struct CodeView {
+    pub code: String,
+    pub validity: Validity,
+    pub next_code: Option<String>,
+}
+
+impl CodeView {
+    /// Current code, next code is added when current one expires in less than `next_threshold`
+    pub fn new(generator: &Generator, next_threshold: Duration) -> Result<Self> {
+        Self::at(generator, otp::now()?, next_threshold)
+    }
+
+    fn at(generator: &Generator, time: u64, next_threshold: Duration) -> Result<Self> {
+        let validity = generator.validity_at(time);
+
+        let next_code = match validity {
+            Validity::Ttl(ttl) if ttl < next_threshold.as_secs() => {
+                Some(generator.generate_with_offset(time, 1)?)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            code: generator.generate_at(time)?,
+            validity,
+            next_code,
+        })
+    }
+
+    /// Code to put to clipboard, next code is taken if it is shown and `copy_next` is set
+    pub fn code_to_copy(&self, copy_next: bool) -> String {
+        match (&self.next_code, copy_next) {
+            (Some(next_code), true) => next_code.clone(),
+            _ => self.code.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CodeView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", group_code(&self.code), self.validity)?;
+        if let Some(next_code) = &self.next_code {
+            write!(f, " next: {}", group_code(next_code))?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits code into groups for readability: `123 456`, `123 456 789`, other codes are split in
+/// halves (`1234 5678`, `123 4567`), codes shorter than 6 characters are left as is
+pub fn group_code(code: &str) -> String {
+    let chars = code.chars().collect::<Vec<_>>();
+
+    match chars.len() {
+        0..6 => code.to_owned(),
+        length if length % 3 == 0 => chars
+            .chunks(3)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join(" "),
+        length => {
+            let (head, tail) = chars.split_at(length / 2);
+            format!(
+                "{} {}",
+                head.iter().collect::<String>(),
+                tail.iter().collect::<String>()
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SecretBytes, Token};
+
+    const THRESHOLD: Duration = Duration::from_secs(5);
+
+    fn generator() -> Generator {
+        let token = Token {
+            digits: 6,
+            period: 30,
+            ..Default::default()
+        };
+        Generator::new(
+            &token,
+            SecretBytes::from(b"12345678901234567890".to_vec()),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn codes_are_grouped() {
+        assert_eq!(group_code("12345"), "12345");
+        assert_eq!(group_code("123456"), "123 456");
+        assert_eq!(group_code("1234567"), "123 4567");
+        assert_eq!(group_code("12345678"), "1234 5678");
+        assert_eq!(group_code("1234567890"), "12345 67890");
+    }
+
+    #[test]
+    fn next_code_is_shown_before_expiry() {
+        let generator = generator();
+
+        let early = CodeView::at(&generator, 59 - 10, THRESHOLD).unwrap();
+        assert_eq!(early.validity, Validity::Ttl(11));
+        assert_eq!(early.next_code, None);
+
+        let late = CodeView::at(&generator, 59 - 4, THRESHOLD).unwrap();
+        assert_eq!(late.validity, Validity::Ttl(5));
+        assert_eq!(late.next_code, None);
+
+        let last = CodeView::at(&generator, 59, THRESHOLD).unwrap();
+        assert_eq!(last.next_code, Some(generator.generate_at(60).unwrap()));
+
+        let longer = CodeView::at(&generator, 59 - 10, Duration::from_secs(15)).unwrap();
+        assert!(longer.next_code.is_some());
+    }
+
+    #[test]
+    fn code_to_copy() {
+        let current = CodeView {
+            code: "123456".to_owned(),
+            validity: Validity::Ttl(20),
+            next_code: None,
+        };
+        assert_eq!(current.code_to_copy(false), "123456");
+        assert_eq!(current.code_to_copy(true), "123456");
+
+        let expiring = CodeView {
+            code: "123456".to_owned(),
+            validity: Validity::Ttl(2),
+            next_code: Some("654321".to_owned()),
+        };
+        assert_eq!(expiring.code_to_copy(false), "123456");
+        assert_eq!(expiring.code_to_copy(true), "654321");
+    }
+}
@@ -1,11 +1,45 @@
 mod add_token;
+mod check_clock;
 mod convert_backup_file;
+mod copy_token;
+mod dedupe_tokens;
+mod edit_token;
+mod list_tokens;
+mod lock_database;
 mod parse_backup_file;
+mod print_code;
+mod print_completions;
+mod print_man;
+mod rekey_database;
+mod remove_token;
+mod rename_token;
+mod run_agent;
+mod seal_database;
+mod show_token;
 mod start_interactive;
+mod unlock_database;
 mod use_token;
+mod verify_token;
 
-pub use add_token::add_token;
-pub use convert_backup_file::convert_backup_file;
+pub use add_token::{TokenParameters, TokenSource, add_token};
+pub use check_clock::check_clock;
+pub use convert_backup_file::{ImportOptions, convert_backup_file};
+pub use copy_token::copy_token;
+pub use dedupe_tokens::dedupe_tokens;
+pub use edit_token::{TokenChanges, edit_token};
+pub use list_tokens::list_tokens;
+pub use lock_database::lock_database;
 pub use parse_backup_file::parse_backup_file;
+pub use print_code::print_code;
+pub use print_completions::print_completions;
+pub use print_man::print_man;
+pub use rekey_database::rekey_database;
+pub use remove_token::remove_token;
+pub use rename_token::rename_token;
+pub use run_agent::run_agent;
+pub use seal_database::seal_database;
+pub use show_token::show_token;
 pub use start_interactive::start_interactive;
+pub use unlock_database::unlock_database;
 pub use use_token::use_token;
+pub use verify_token::verify_token;
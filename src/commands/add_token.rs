@@ -1,99 +1,154 @@
-use anyhow::{Result, anyhow};
-use argon2::Argon2;
-use chacha20poly1305::{
-    AeadCore, KeyInit, XChaCha20Poly1305,
-    aead::{Aead, OsRng},
+use anyhow::Result;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{
+    agent::open_or_create_vault,
+    cli::SecretEncoding,
+    error::Error,
+    otp::{MAX_DIGITS, MIN_DIGITS},
+    parsers::parse_otpauth_url,
+    prompt::read_password,
+    types::{SecretBytes, SecretString, Token, TokenAlgorithm, TokenType},
 };
-use rand::{self, Rng, rngs::StdRng};
-use std::collections::BTreeMap;
-use std::fs;
-use std::io::{Write, stdin, stdout};
-use std::path::PathBuf;
-use termion::input::TermRead;
-use toml;
-use totp_rs::{Algorithm, TOTP};
-
-use crate::types::{KeyFile, Token, TokenAlgorithm, TokenType};
-
-pub fn add_token(key_file: &PathBuf, token_label: String, token_url: &str) -> Result<()> {
-    let stdout = stdout();
-    let mut stdout = stdout.lock();
-    let stdin = stdin();
-    let mut stdin = stdin.lock();
-
-    stdout.write_all(b"Enter database password: ")?;
-    stdout.flush().unwrap();
-    let output_password = stdin.read_passwd(&mut stdout)?.unwrap();
-    stdout.write_all(b"\n")?;
-
-    std::mem::drop(stdout);
-
-    let mut rng: StdRng = rand::make_rng();
-
-    let mut key_file_contents = match key_file.exists() {
-        true => {
-            let key_file_data = fs::read_to_string(key_file)?;
-            toml::from_str::<KeyFile>(key_file_data.as_str())?
-        }
-        false => {
-            let mut salt = [0u8; 32];
-            rng.fill_bytes(&mut salt);
 
-            KeyFile {
-                master_key_salt: salt.to_vec(),
-                tokens: BTreeMap::new(),
+/// Where to take new token from
+pub enum TokenSource {
+    /// Token otpauth url
+    Url(String),
+    /// Raw token secret with parameters specified separately
+    Secret {
+        secret: SecretString,
+        encoding: SecretEncoding,
+        parameters: TokenParameters,
+    },
+    /// Secret (or otpauth url) is asked interactively without echo
+    Prompt {
+        encoding: SecretEncoding,
+        parameters: TokenParameters,
+    },
+}
+
+/// Token parameters used when token is added from raw secret
+pub struct TokenParameters {
+    pub issuer: String,
+    pub label: String,
+    pub digits: u32,
+    pub period: u64,
+    pub algorithm: TokenAlgorithm,
+    pub token_type: TokenType,
+    pub counter: u32,
+}
+
+pub fn add_token(
+    key_file: &Path,
+    token_label: String,
+    source: TokenSource,
+    overwrite: bool,
+) -> Result<()> {
+    let mut token = match source {
+        TokenSource::Url(url) => parse_otpauth_url(url.as_str())?,
+        TokenSource::Secret {
+            secret,
+            encoding,
+            parameters,
+        } => token_from_secret(&secret, encoding, parameters)?,
+        TokenSource::Prompt {
+            encoding,
+            parameters,
+        } => {
+            let input = read_password("Enter token secret or otpauth url: ")?;
+            if input
+                .as_str()
+                .trim()
+                .to_lowercase()
+                .starts_with("otpauth://")
+            {
+                parse_otpauth_url(input.as_str())?
+            } else {
+                token_from_secret(&input, encoding, parameters)?
             }
         }
     };
 
-    let mut encryption_key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(
-            output_password.as_bytes(),
-            key_file_contents.master_key_salt.as_slice(),
-            &mut encryption_key,
-        )
-        .map_err(|err| anyhow!("{}", err))?;
+    if token.token_type.requires_pin() {
+        let pin = read_password("Enter token PIN: ")?;
+        token.pin = Some(SecretBytes::from(pin.as_bytes().to_vec()));
+    }
 
-    let cipher = XChaCha20Poly1305::new_from_slice(encryption_key.as_slice())?;
+    let mut vault = open_or_create_vault(key_file)?;
 
-    let token = {
-        let token =
-            TOTP::from_url_unchecked(token_url.trim().replace("algorithm=sha", "algorithm=SHA"))
-                .map_err(|err| anyhow!("Failed to parse token from url: {}", err))?;
+    if !overwrite && vault.key_file.tokens.contains_key(&token_label) {
+        Err(Error::TokenExists(token_label.clone()))?;
+    }
 
-        let algorithm = match token.algorithm {
-            Algorithm::SHA1 => TokenAlgorithm::Sha1,
-            Algorithm::SHA256 => TokenAlgorithm::Sha256,
-            Algorithm::SHA512 => TokenAlgorithm::Sha512,
-        };
+    vault.encrypt_token(&token_label, &mut token)?;
 
-        let token_type = TokenType::Totp;
+    vault.key_file.tokens.insert(token_label, token);
 
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
-        let mut key = cipher.encrypt(&nonce, token.secret.as_slice()).unwrap();
-        let mut encrypted_key = Vec::new();
-        encrypted_key.extend_from_slice(nonce.as_slice());
-        encrypted_key.append(&mut key);
+    vault.save()?;
+
+    println!("Database saved");
+    Ok(())
+}
 
-        Ok::<_, anyhow::Error>(Token {
-            algorithm,
-            counter: 0,
-            digits: token.digits as u32,
-            issuer: token.issuer.unwrap_or_default(),
-            label: token.account_name,
-            period: token.step,
-            token_type,
-            key: encrypted_key,
-        })
-    }?;
+fn token_from_secret(
+    secret: &SecretString,
+    encoding: SecretEncoding,
+    parameters: TokenParameters,
+) -> Result<Token> {
+    if parameters.period == 0 {
+        Err(Error::InvalidArgument(
+            "Token period must be greater than zero".to_owned(),
+        ))?;
+    }
+
+    if !(MIN_DIGITS..=MAX_DIGITS).contains(&parameters.digits) {
+        Err(Error::InvalidArgument(format!(
+            "Token digits must be in range from {} to {}",
+            MIN_DIGITS, MAX_DIGITS
+        )))?;
+    }
+
+    let mut token = Token {
+        algorithm: parameters.algorithm,
+        counter: parameters.counter,
+        digits: parameters.digits,
+        issuer: parameters.issuer,
+        label: parameters.label,
+        period: parameters.period,
+        key: decode_secret(secret, encoding)?,
+        ..Default::default()
+    };
 
-    key_file_contents.tokens.insert(token_label, token);
+    token.set_type(parameters.token_type);
 
-    let serialized_file = toml::to_string(&key_file_contents)?;
+    Ok(token)
+}
 
-    fs::write(key_file, serialized_file)?;
+/// Decodes secret ignoring whitespace, dashes and padding that sites add for readability
+fn decode_secret(secret: &SecretString, encoding: SecretEncoding) -> Result<SecretBytes> {
+    let normalized = Zeroizing::new(
+        secret
+            .as_str()
+            .chars()
+            .filter(|ch| !ch.is_whitespace() && *ch != '-' && *ch != '=')
+            .collect::<String>(),
+    );
+
+    if normalized.is_empty() {
+        Err(Error::parse("Token secret is empty"))?;
+    }
+
+    let bytes = match encoding {
+        SecretEncoding::Base32 => base32::decode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            &normalized.to_uppercase(),
+        )
+        .ok_or_else(|| Error::parse("Token secret is not valid base32"))?,
+        SecretEncoding::Hex => hex::decode(normalized.as_str())
+            .map_err(|err| Error::parse(format!("Token secret is not valid hex: {}", err)))?,
+    };
 
-    println!("Database saved");
-    Ok(())
+    Ok(bytes.into())
 }
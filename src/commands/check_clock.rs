@@ -0,0 +1,26 @@
+use anyhow::Result;
+
+use crate::{
+    clock::{TIMEOUT, drift_warning, query_server, reference_drift},
+    error::Error,
+};
+
+/// Compares system clock with reference time or SNTP server, fails if drift breaks TOTP codes
+pub fn check_clock(reference: Option<u64>, server: &str) -> Result<()> {
+    let (drift, source) = match reference {
+        Some(reference) => (reference_drift(reference)?, "reference time".to_owned()),
+        None => (query_server(server, TIMEOUT)?, server.to_owned()),
+    };
+
+    println!(
+        "System clock is {:.3} seconds {} {}",
+        drift.abs(),
+        if drift > 0.0 { "ahead of" } else { "behind" },
+        source
+    );
+
+    match drift_warning(drift) {
+        Some(warning) => Err(Error::ClockDrift(warning))?,
+        None => Ok(()),
+    }
+}
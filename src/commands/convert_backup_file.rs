@@ -1,108 +1,649 @@
-use anyhow::{Result, anyhow};
-use argon2::Argon2;
-use chacha20poly1305::{
-    AeadCore, KeyInit, XChaCha20Poly1305,
-    aead::{Aead, OsRng},
-};
-use rand::{self, Rng, rngs::StdRng};
-use std::collections::BTreeMap;
-use std::fs;
-use std::io::{Write, stdin, stdout};
-use std::path::PathBuf;
-use termion::input::TermRead;
-use toml;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
 
 use crate::{
-    parsers::*,
-    types::{BackupType, KeyFile},
+    agent::{cached_vault, open_or_create_vault},
+    cli::{BackupType, ConflictStrategy, DuplicateStrategy},
+    error::Error,
+    prompt::{read_line, read_password},
+    types::{SecretBytes, Token, TokenType},
+    vault::{Vault, token_fingerprint},
 };
 
+mod select_tokens;
+
+use super::parse_backup_file::read_backup;
+use select_tokens::select_tokens;
+
+/// Characters trimmed from token names when template placeholder is empty
+const NAME_SEPARATORS: &[char] = &['-', '_', '.', ':', ' '];
+
+/// How imported tokens are named and stored
+pub struct ImportOptions {
+    /// Template for token names, supports `{issuer}`, `{label}` and `{type}` placeholders
+    pub name_template: String,
+    /// What to do when token with the same name already exists
+    pub on_conflict: ConflictStrategy,
+    /// What to do when token with the same secret and parameters already exists
+    pub on_duplicate: DuplicateStrategy,
+    /// Only report what would be done, database is not written
+    pub dry_run: bool,
+    /// Let user pick tokens to import and change their names before import
+    pub select: bool,
+    /// Import valid entries of tokens list or Google Authenticator backup, invalid ones are
+    /// reported instead of failing import
+    pub skip_invalid: bool,
+}
+
 pub fn convert_backup_file(
     backup_type: &BackupType,
-    input_file: &PathBuf,
-    key_file: &PathBuf,
+    input_file: &Path,
+    key_file: &Path,
+    options: &ImportOptions,
 ) -> Result<()> {
-    let stdout = stdout();
-    let mut stdout = stdout.lock();
-    let stdin = stdin();
-    let mut stdin = stdin.lock();
+    // Fail on invalid template before asking for any passwords
+    validate_name_template(&options.name_template)?;
 
-    let tokens = match backup_type {
-        BackupType::TokensList => {
-            stdout.write_all(b"parsing tokens list...\n")?;
-            stdout.flush().unwrap();
+    let parsed = read_backup(backup_type, input_file, options.skip_invalid)?;
 
-            parse_tokens_list(input_file)
+    let mut candidates = parsed
+        .tokens
+        .into_iter()
+        .map(|token| (render_token_name(&options.name_template, &token), token))
+        .collect::<Vec<_>>();
+
+    if options.select {
+        if candidates.is_empty() {
+            println!("Nothing to import");
+            return Ok(());
+        }
+        candidates = select_tokens(candidates)?;
+
+        if candidates.is_empty() {
+            println!("No tokens selected");
+            return Ok(());
         }
-        BackupType::FreeOtp => {
-            stdout.write_all(b"Enter backup file password: ")?;
-            stdout.flush().unwrap();
-            let input_password = stdin.read_passwd(&mut stdout)?.unwrap();
-            stdout.write_all(b"\nparsing backup...\n")?;
-            stdout.flush().unwrap();
+    }
+
+    let mut summary = ImportSummary {
+        rejected: parsed.rejected.iter().map(ToString::to_string).collect(),
+        ..Default::default()
+    };
+
+    if options.dry_run {
+        return plan_import(key_file, candidates, options, summary);
+    }
 
-            parse_freeotp_backup(input_file, input_password.as_str())
+    // Backups do not carry PINs, they are asked before database password
+    for (name, token) in candidates.iter_mut() {
+        if token.token_type.requires_pin() && token.pin.is_none() {
+            let pin = read_password(&format!("Enter PIN of token {}: ", name))?;
+            token.pin = Some(SecretBytes::from(pin.as_bytes().to_vec()));
         }
-        BackupType::GoogleAuth => {
-            stdout.write_all(b"parsing backup...\n")?;
-            stdout.flush().unwrap();
+    }
 
-            parse_googleauth_backup(input_file)
+    let mut vault = open_or_create_vault(key_file)?;
+
+    // Existing tokens are decrypted only when duplicates have to be detected
+    let known_fingerprints = match options.on_duplicate {
+        DuplicateStrategy::Keep => HashMap::new(),
+        _ => vault
+            .fingerprints()?
+            .into_iter()
+            .map(|(name, fingerprint)| (fingerprint, name))
+            .collect(),
+    };
+
+    let written = import_tokens(
+        &mut vault.key_file.tokens,
+        Some(known_fingerprints),
+        candidates,
+        options,
+        &mut summary,
+    )?;
+    for name in written {
+        if let Some(mut token) = vault.key_file.tokens.remove(&name) {
+            vault.encrypt_token(&name, &mut token)?;
+            vault.key_file.tokens.insert(name, token);
         }
-    }?;
+    }
 
-    stdout.write_all(b"Enter database password: ")?;
-    stdout.flush().unwrap();
-    let output_password = stdin.read_passwd(&mut stdout)?.unwrap();
-    stdout.write_all(b"\n")?;
+    summary.print();
 
-    std::mem::drop(stdout);
+    if summary.added.is_empty() && summary.updated.is_empty() && summary.merged.is_empty() {
+        println!("Nothing to save");
+        return Ok(());
+    }
 
-    let mut rng: StdRng = rand::make_rng();
+    vault.save()?;
+
+    println!("Database saved");
+    Ok(())
+}
 
-    let mut key_file_contents = match key_file.exists() {
-        true => {
-            let key_file_data = fs::read_to_string(key_file)?;
-            toml::from_str::<KeyFile>(key_file_data.as_str())?
+/// Reports what import would do without asking anything: database is read with key cached by
+/// agent, or only its metadata is read, then duplicates are not detected. PINs are not asked, so
+/// duplicates of tokens with PIN are not detected either, and conflicts that would be asked about
+/// are reported
+fn plan_import(
+    key_file: &Path,
+    candidates: Vec<(String, Token)>,
+    options: &ImportOptions,
+    mut summary: ImportSummary,
+) -> Result<()> {
+    let (mut tokens, known_fingerprints) = match cached_vault(key_file) {
+        Some(vault) => {
+            let known_fingerprints = match options.on_duplicate {
+                DuplicateStrategy::Keep => HashMap::new(),
+                _ => vault
+                    .fingerprints()?
+                    .into_iter()
+                    .map(|(name, fingerprint)| (fingerprint, name))
+                    .collect(),
+            };
+            (vault.key_file.tokens, Some(known_fingerprints))
+        }
+        None if key_file.exists() => {
+            let vault = Vault::open_metadata(key_file, |_| {
+                Err(Error::CredentialsRequired(
+                    "Database is sealed, unlock it in agent to make dry run".to_owned(),
+                ))
+            })?;
+            let known_fingerprints = match options.on_duplicate {
+                DuplicateStrategy::Keep => Some(HashMap::new()),
+                _ => None,
+            };
+            (vault.key_file.tokens, known_fingerprints)
         }
-        false => {
-            let mut salt = [0u8; 32];
-            rng.fill_bytes(&mut salt);
+        None => (BTreeMap::new(), Some(HashMap::new())),
+    };
+
+    let unchecked_pins = known_fingerprints.is_some()
+        && options.on_duplicate != DuplicateStrategy::Keep
+        && candidates
+            .iter()
+            .any(|(_, token)| token.token_type.requires_pin() && token.pin.is_none());
+    let duplicates_checked = known_fingerprints.is_some();
+
+    import_tokens(
+        &mut tokens,
+        known_fingerprints,
+        candidates,
+        options,
+        &mut summary,
+    )?;
 
-            KeyFile {
-                master_key_salt: salt.to_vec(),
-                tokens: BTreeMap::new(),
+    summary.print();
+    if !duplicates_checked {
+        println!("Duplicates are not detected, database key is not cached by agent");
+    }
+    if unchecked_pins {
+        println!("Duplicates of tokens with PIN are not detected, PINs are not asked");
+    }
+    println!("Dry run, database is not saved");
+    Ok(())
+}
+
+/// Adds candidates to tokens according to options, raw keys are left for caller to encrypt.
+/// Duplicates are detected only if fingerprints of existing tokens are known. Returns names of
+/// added and overwritten tokens
+fn import_tokens(
+    tokens: &mut BTreeMap<String, Token>,
+    mut known_fingerprints: Option<HashMap<[u8; 32], String>>,
+    candidates: Vec<(String, Token)>,
+    options: &ImportOptions,
+    summary: &mut ImportSummary,
+) -> Result<BTreeSet<String>> {
+    let mut written = BTreeSet::new();
+
+    for (name, mut token) in candidates.into_iter() {
+        // PIN is part of fingerprint, so it can't be computed when PIN was not asked
+        let fingerprint = (!token.token_type.requires_pin() || token.pin.is_some())
+            .then(|| token_fingerprint(&token, &token.key, token.pin.as_deref()));
+
+        if let (Some(known_fingerprints), Some(fingerprint)) = (&known_fingerprints, &fingerprint)
+            && let Some(existing_name) = known_fingerprints.get(fingerprint)
+        {
+            match options.on_duplicate {
+                DuplicateStrategy::Skip => {
+                    summary
+                        .skipped
+                        .push((name, format!("duplicate of {}", existing_name)));
+                    continue;
+                }
+                DuplicateStrategy::Merge => {
+                    if let Some(existing) = tokens.get_mut(existing_name)
+                        && merge_token(existing, &token)
+                    {
+                        summary.merged.push((name, existing_name.to_owned()));
+                    } else {
+                        summary
+                            .skipped
+                            .push((name, format!("duplicate of {}", existing_name)));
+                    }
+                    continue;
+                }
+                DuplicateStrategy::Keep => {}
             }
         }
-    };
 
-    let mut encryption_key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(
-            output_password.as_bytes(),
-            key_file_contents.master_key_salt.as_slice(),
-            &mut encryption_key,
-        )
-        .map_err(|err| anyhow!("{}", err))?;
+        let written_name =
+            match resolve_conflict(tokens, name.clone(), options.on_conflict, options.dry_run)? {
+                Resolution::Add(new_name) => {
+                    tokens.insert(new_name.clone(), token);
+                    summary
+                        .added
+                        .push((new_name.clone(), (new_name != name).then_some(name)));
+                    new_name
+                }
+                Resolution::Overwrite(name) => {
+                    // Tags, group and the rest are set locally, backups do not carry them
+                    if let Some(existing) = tokens.get(&name) {
+                        token.tags = existing.tags.clone();
+                        token.group = existing.group.clone();
+                        token.favorite = existing.favorite;
+                        token.notes = existing.notes.clone();
+                        token.sort_order = existing.sort_order;
+                    }
+                    tokens.insert(name.clone(), token);
+                    if let Some(known_fingerprints) = known_fingerprints.as_mut() {
+                        known_fingerprints.retain(|_, known_name| *known_name != name);
+                    }
+                    summary.updated.push(name.clone());
+                    name
+                }
+                Resolution::Skip(name) => {
+                    summary
+                        .skipped
+                        .push((name, "name already exists".to_owned()));
+                    continue;
+                }
+                Resolution::Ask(name) => {
+                    summary.conflicts.push(name);
+                    continue;
+                }
+            };
+
+        if let (Some(known_fingerprints), Some(fingerprint)) =
+            (known_fingerprints.as_mut(), fingerprint)
+        {
+            known_fingerprints.insert(fingerprint, written_name.clone());
+        }
+        written.insert(written_name);
+    }
+
+    Ok(written)
+}
+
+#[derive(Default)]
+struct ImportSummary {
+    /// Token name with original name if token was renamed due to name conflict
+    added: Vec<(String, Option<String>)>,
+    updated: Vec<String>,
+    /// Imported token name with name of existing token it was merged into
+    merged: Vec<(String, String)>,
+    /// Imported token name with reason it was skipped
+    skipped: Vec<(String, String)>,
+    /// Backup entries that could not be parsed
+    rejected: Vec<String>,
+    /// Names that already exist in database, dry run only reports that user would be asked
+    conflicts: Vec<String>,
+}
 
-    let cipher = XChaCha20Poly1305::new_from_slice(encryption_key.as_slice())?;
+impl ImportSummary {
+    fn print(&self) {
+        for (name, original_name) in self.added.iter() {
+            match original_name {
+                Some(original_name) => {
+                    println!("added: {} (renamed from {})", name, original_name)
+                }
+                None => println!("added: {}", name),
+            }
+        }
+        for name in self.updated.iter() {
+            println!("updated: {}", name);
+        }
+        for (name, existing_name) in self.merged.iter() {
+            println!("merged: {} into {}", name, existing_name);
+        }
+        for (name, reason) in self.skipped.iter() {
+            println!("skipped: {} ({})", name, reason);
+        }
+        for reason in self.rejected.iter() {
+            println!("rejected: {}", reason);
+        }
+        for name in self.conflicts.iter() {
+            println!("conflict: {} (would be asked what to do)", name);
+        }
 
-    tokens.into_iter().for_each(|mut token| {
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
-        let mut key = cipher.encrypt(&nonce, token.key.as_slice()).unwrap();
-        let mut encrypted_key = Vec::new();
-        encrypted_key.extend_from_slice(nonce.as_slice());
-        encrypted_key.append(&mut key);
-        token.key = encrypted_key;
-        key_file_contents
-            .tokens
-            .insert(format!("{}-{}", token.issuer, token.label), token);
-    });
+        println!(
+            "Added: {}, updated: {}, merged: {}, skipped: {}, rejected: {}",
+            self.added.len(),
+            self.updated.len(),
+            self.merged.len(),
+            self.skipped.len(),
+            self.rejected.len()
+        );
+        if !self.conflicts.is_empty() {
+            println!("Conflicts: {}", self.conflicts.len());
+        }
+    }
+}
 
-    let serialized_file = toml::to_string(&key_file_contents)?;
+/// Fills in metadata missing in existing token from its imported duplicate, returns `true` if
+/// anything changed
+fn merge_token(existing: &mut Token, imported: &Token) -> bool {
+    let mut changed = false;
 
-    fs::write(key_file, serialized_file)?;
+    if existing.issuer.is_empty() && !imported.issuer.is_empty() {
+        existing.issuer = imported.issuer.clone();
+        changed = true;
+    }
+    if existing.label.is_empty() && !imported.label.is_empty() {
+        existing.label = imported.label.clone();
+        changed = true;
+    }
+    for tag in imported.tags.iter() {
+        if !existing.tags.contains(tag) {
+            existing.tags.push(tag.clone());
+            changed = true;
+        }
+    }
+    if existing.group.is_none() && imported.group.is_some() {
+        existing.group = imported.group.clone();
+        changed = true;
+    }
+    // HOTP counter only moves forward, going back would make server reject codes
+    if existing.counter < imported.counter {
+        existing.counter = imported.counter;
+        changed = true;
+    }
+
+    changed
+}
+
+enum Resolution {
+    Add(String),
+    Overwrite(String),
+    Skip(String),
+    /// User would be asked, only returned in dry run
+    Ask(String),
+}
+
+fn resolve_conflict(
+    existing: &BTreeMap<String, Token>,
+    name: String,
+    strategy: ConflictStrategy,
+    dry_run: bool,
+) -> Result<Resolution> {
+    if !existing.contains_key(&name) {
+        return Ok(Resolution::Add(name));
+    }
+
+    match strategy {
+        ConflictStrategy::Skip => Ok(Resolution::Skip(name)),
+        ConflictStrategy::Overwrite => Ok(Resolution::Overwrite(name)),
+        ConflictStrategy::Suffix => Ok(Resolution::Add(suffixed_name(existing, &name))),
+        ConflictStrategy::Prompt if dry_run => Ok(Resolution::Ask(name)),
+        ConflictStrategy::Prompt => loop {
+            let answer = read_line(&format!(
+                "Token {} already exists: (s)kip, (o)verwrite, (a)dd with suffix or (r)ename: ",
+                name
+            ))?;
+
+            match answer.trim() {
+                "s" => return Ok(Resolution::Skip(name)),
+                "o" => return Ok(Resolution::Overwrite(name)),
+                "a" => return Ok(Resolution::Add(suffixed_name(existing, &name))),
+                // New name is asked separately, so it may be any string including answer letters
+                "r" => match read_line("Enter new token name (empty to go back): ")?.trim() {
+                    "" => {}
+                    new_name if existing.contains_key(new_name) => {
+                        println!("Token {} already exists too", new_name);
+                    }
+                    new_name => return Ok(Resolution::Add(new_name.to_owned())),
+                },
+                _ => {}
+            }
+        },
+    }
+}
+
+/// Picks first free name in `{name}-2`, `{name}-3`, ... sequence
+fn suffixed_name(existing: &BTreeMap<String, Token>, name: &str) -> String {
+    (2..)
+        .map(|index| format!("{}-{}", name, index))
+        .find(|candidate| !existing.contains_key(candidate))
+        .unwrap()
+}
+
+fn validate_name_template(template: &str) -> Result<()> {
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "Unclosed placeholder in name template: {}",
+                template
+            ))
+        })?;
+
+        match &rest[start + 1..start + end] {
+            "issuer" | "label" | "type" => {}
+            placeholder => Err(Error::InvalidArgument(format!(
+                "Unknown placeholder {{{}}} in name template, supported ones are {{issuer}}, {{label}} and {{type}}",
+                placeholder
+            )))?,
+        }
+
+        rest = &rest[start + end + 1..];
+    }
 
-    println!("Database saved");
     Ok(())
 }
+
+/// Renders token name from template, separators left over from empty placeholders are removed,
+/// so `{issuer}-{label}` becomes `alice` rather than `-alice` for token without issuer. `/` is
+/// replaced with `-`, since it separates profile from token name
+fn render_token_name(template: &str, token: &Token) -> String {
+    let token_type = match token.token_type {
+        TokenType::Hotp => "hotp",
+        TokenType::Totp => "totp",
+        TokenType::Steam => "steam",
+        TokenType::Motp => "motp",
+        TokenType::Yandex => "yandex",
+    };
+
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+
+        let end = start + rest[start..].find('}').unwrap();
+        let value = match &rest[start + 1..end] {
+            "issuer" => token.issuer.trim(),
+            "label" => token.label.trim(),
+            _ => token_type,
+        };
+
+        if value.is_empty() {
+            // Drop separator that would be doubled or left dangling
+            rest = rest[end + 1..].trim_start_matches(NAME_SEPARATORS);
+            continue;
+        }
+
+        name.push_str(value);
+        rest = &rest[end + 1..];
+    }
+
+    name.push_str(rest);
+
+    let name = name.replace('/', "-");
+    let name = name.trim_matches(NAME_SEPARATORS);
+
+    match name.is_empty() {
+        true => "token".to_owned(),
+        false => name.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(issuer: &str, label: &str, token_type: TokenType) -> Token {
+        Token {
+            issuer: issuer.to_owned(),
+            label: label.to_owned(),
+            token_type,
+            ..Default::default()
+        }
+    }
+
+    fn existing(names: &[&str]) -> BTreeMap<String, Token> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), Token::default()))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_rendered() {
+        let token = token("GitHub", "alice", TokenType::Hotp);
+
+        assert_eq!(
+            render_token_name("{issuer}-{label}", &token),
+            "GitHub-alice"
+        );
+        assert_eq!(
+            render_token_name("{type}/{label}@{issuer}", &token),
+            "hotp-alice@GitHub"
+        );
+        assert_eq!(render_token_name("work", &token), "work");
+    }
+
+    #[test]
+    fn empty_placeholders_are_trimmed() {
+        assert_eq!(
+            render_token_name("{issuer}-{label}", &token("", "alice", TokenType::Totp)),
+            "alice"
+        );
+        assert_eq!(
+            render_token_name("{issuer}-{label}", &token("GitHub", " ", TokenType::Totp)),
+            "GitHub"
+        );
+        assert_eq!(
+            render_token_name(
+                "{issuer}_{label}.{type}",
+                &token("", "alice", TokenType::Steam)
+            ),
+            "alice.steam"
+        );
+        assert_eq!(
+            render_token_name("{issuer}-{label}", &token("", "", TokenType::Totp)),
+            "token"
+        );
+        assert_eq!(
+            render_token_name("{issuer}/{label}", &token("", "alice", TokenType::Totp)),
+            "alice"
+        );
+        assert_eq!(
+            render_token_name("{issuer}-{label}", &token("A/B", "alice", TokenType::Totp)),
+            "A-B-alice"
+        );
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(validate_name_template("{issuer}-{label}_{type}").is_ok());
+        assert!(validate_name_template("plain").is_ok());
+
+        assert_eq!(
+            validate_name_template("{issuer}-{label")
+                .unwrap_err()
+                .to_string(),
+            "Unclosed placeholder in name template: {issuer}-{label"
+        );
+        assert!(
+            validate_name_template("{issuer}-{account}")
+                .unwrap_err()
+                .to_string()
+                .starts_with("Unknown placeholder {account}")
+        );
+    }
+
+    #[test]
+    fn existing_metadata_wins_on_merge() {
+        let mut existing = Token {
+            label: String::from("alice"),
+            counter: 5,
+            tags: vec![String::from("work")],
+            group: Some(String::from("dev")),
+            ..Default::default()
+        };
+        let imported = Token {
+            issuer: String::from("GitHub"),
+            label: String::from("bob"),
+            counter: 3,
+            tags: vec![String::from("personal"), String::from("work")],
+            group: Some(String::from("other")),
+            ..Default::default()
+        };
+
+        assert!(merge_token(&mut existing, &imported));
+        // Only missing fields are taken from imported token, tags are united
+        assert_eq!(existing.issuer, "GitHub");
+        assert_eq!(existing.label, "alice");
+        assert_eq!(existing.tags, ["work", "personal"]);
+        assert_eq!(existing.group.as_deref(), Some("dev"));
+        // Counter never goes back
+        assert_eq!(existing.counter, 5);
+
+        let newer = Token {
+            counter: 9,
+            ..imported.clone()
+        };
+        assert!(merge_token(&mut existing, &newer));
+        assert_eq!(existing.counter, 9);
+
+        assert!(!merge_token(&mut existing, &imported));
+    }
+
+    #[test]
+    fn colliding_names_are_suffixed() {
+        let existing = existing(&["github", "github-2", "github-4"]);
+
+        assert_eq!(suffixed_name(&existing, "github"), "github-3");
+        assert_eq!(suffixed_name(&existing, "gitlab"), "gitlab-2");
+    }
+
+    #[test]
+    fn conflicts_are_resolved() {
+        let existing = existing(&["github", "github-2"]);
+        let resolve = |name: &str, strategy| match resolve_conflict(
+            &existing,
+            name.to_owned(),
+            strategy,
+            true,
+        )
+        .unwrap()
+        {
+            Resolution::Add(name) => format!("add {}", name),
+            Resolution::Overwrite(name) => format!("overwrite {}", name),
+            Resolution::Skip(name) => format!("skip {}", name),
+            Resolution::Ask(name) => format!("ask {}", name),
+        };
+
+        assert_eq!(resolve("gitlab", ConflictStrategy::Skip), "add gitlab");
+        assert_eq!(resolve("github", ConflictStrategy::Skip), "skip github");
+        assert_eq!(
+            resolve("github", ConflictStrategy::Overwrite),
+            "overwrite github"
+        );
+        assert_eq!(resolve("github", ConflictStrategy::Suffix), "add github-3");
+        // Dry run does not ask
+        assert_eq!(resolve("github", ConflictStrategy::Prompt), "ask github");
+    }
+}
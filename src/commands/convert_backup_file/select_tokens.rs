@@ -0,0 +1,153 @@
+use anyhow::Result;
+use std::io::{stdin, stdout};
+use termion::{event::Key, input::TermRead, raw::IntoRawMode};
+
+use crate::{buffered_stdout::BufferedStdout, error::Error, types::Token};
+
+/// Amount of tokens visible on screen at once
+const WINDOW_SIZE: usize = 10;
+
+/// Shows checklist of parsed tokens, returns selected tokens with names set by user
+pub fn select_tokens(candidates: Vec<(String, Token)>) -> Result<Vec<(String, Token)>> {
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let stdout_handle = stdout().into_raw_mode().map_err(Error::Terminal)?;
+    let mut buffered_stdout = BufferedStdout::new(stdout());
+
+    let mut candidates = candidates
+        .into_iter()
+        .map(|(name, token)| Candidate {
+            name,
+            token,
+            selected: true,
+        })
+        .collect::<Vec<_>>();
+    let mut cursor = 0;
+    let mut mode = SelectMode::Checklist;
+
+    render(&candidates, cursor, &mode, &mut buffered_stdout)?;
+
+    for key in stdin().keys() {
+        match mode {
+            SelectMode::Checklist => match key? {
+                Key::Esc | Key::Ctrl('c') | Key::Char('q') => {
+                    buffered_stdout.clear()?;
+                    return Ok(Vec::new());
+                }
+                Key::Char('\n') => break,
+                Key::Up if cursor > 0 => cursor -= 1,
+                Key::Down if cursor + 1 < candidates.len() => cursor += 1,
+                Key::Char(' ') if !candidates.is_empty() => {
+                    candidates[cursor].selected = !candidates[cursor].selected
+                }
+                Key::Char('a') => {
+                    let select_all = candidates.iter().any(|candidate| !candidate.selected);
+                    candidates
+                        .iter_mut()
+                        .for_each(|candidate| candidate.selected = select_all);
+                }
+                Key::Char('r') if !candidates.is_empty() => {
+                    mode = SelectMode::Rename(candidates[cursor].name.clone())
+                }
+                _ => {}
+            },
+            SelectMode::Rename(ref mut name_buf) => match key? {
+                Key::Esc | Key::Ctrl('c') => mode = SelectMode::Checklist,
+                Key::Char('\n') => {
+                    if !name_buf.trim().is_empty() {
+                        candidates[cursor].name = name_buf.trim().to_owned();
+                        candidates[cursor].selected = true;
+                    }
+                    mode = SelectMode::Checklist;
+                }
+                Key::Backspace => {
+                    name_buf.pop();
+                }
+                Key::Char(ch) => name_buf.push(ch),
+                _ => {}
+            },
+        }
+
+        render(&candidates, cursor, &mode, &mut buffered_stdout)?;
+    }
+
+    buffered_stdout.clear()?;
+    std::mem::drop(stdout_handle);
+
+    Ok(candidates
+        .into_iter()
+        .filter(|candidate| candidate.selected)
+        .map(|candidate| (candidate.name, candidate.token))
+        .collect())
+}
+
+struct Candidate {
+    name: String,
+    token: Token,
+    selected: bool,
+}
+
+enum SelectMode {
+    Checklist,
+    /// Name of token under cursor is being edited, holds entered name
+    Rename(String),
+}
+
+fn render(
+    candidates: &[Candidate],
+    cursor: usize,
+    mode: &SelectMode,
+    buffered_stdout: &mut BufferedStdout,
+) -> Result<()> {
+    buffered_stdout.add(&format!(
+        "Select tokens to import ({} of {} selected):\r\n\r\n",
+        candidates
+            .iter()
+            .filter(|candidate| candidate.selected)
+            .count(),
+        candidates.len()
+    ));
+
+    let window_start = cursor
+        .saturating_sub(WINDOW_SIZE / 2)
+        .min(candidates.len().saturating_sub(WINDOW_SIZE));
+
+    for (index, candidate) in candidates
+        .iter()
+        .enumerate()
+        .skip(window_start)
+        .take(WINDOW_SIZE)
+    {
+        buffered_stdout.add(&format!(
+            " {} [{}] {} ({} {})\r\n",
+            if index == cursor { '>' } else { ' ' },
+            if candidate.selected { 'x' } else { ' ' },
+            candidate.name,
+            candidate.token.issuer,
+            candidate.token.label,
+        ));
+    }
+
+    buffered_stdout.add("\r\n");
+
+    match mode {
+        SelectMode::Checklist => {
+            buffered_stdout
+                .add("use up and down arrows to move, press 'space' to toggle token,\r\n");
+            buffered_stdout.add("press 'a' to toggle all tokens, press 'r' to rename token,\r\n");
+            buffered_stdout
+                .add("press 'Enter' to import selected tokens, press 'q' or 'Esc' to cancel\r\n");
+        }
+        SelectMode::Rename(name_buf) => {
+            buffered_stdout.add("press 'Enter' to confirm, press 'Esc' to cancel renaming\r\n");
+            buffered_stdout.add(&format!("Enter new token name: {}\r\n", name_buf));
+        }
+    }
+
+    buffered_stdout.clear()?;
+    buffered_stdout.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use anyhow::Result;
+use std::fs;
+
+use crate::{config::Config, error::Error};
+
+/// Copies (or moves) token between databases of two profiles. Token key is decrypted with key of
+/// source database and encrypted again with key of destination one
+pub fn copy_token(
+    config: &Config,
+    source: &str,
+    destination: &str,
+    force: bool,
+    remove_source: bool,
+) -> Result<()> {
+    let (source_profile, name) = source.split_once('/').ok_or_else(|| {
+        Error::InvalidArgument("Token must be referenced as profile/name".to_owned())
+    })?;
+    let (destination_profile, new_name) =
+        destination.split_once('/').unwrap_or((destination, name));
+
+    let source_database = config.profile(source_profile)?;
+    let destination_database = config.profile(destination_profile)?;
+    if fs::canonicalize(&source_database.path)? == fs::canonicalize(&destination_database.path)? {
+        Err(Error::InvalidArgument(
+            "Token can't be copied to the same database, use `rename` command instead".to_owned(),
+        ))?;
+    }
+
+    let mut source_vault = source_database.open_cached()?;
+    let token = source_vault
+        .key_file
+        .tokens
+        .get(name)
+        .ok_or_else(|| Error::TokenNotFound(name.to_owned()))?;
+
+    let mut copy = token.clone();
+    copy.key = source_vault.decrypt_secret(name, token)?;
+    copy.pin = source_vault.decrypt_pin(name, token)?;
+
+    let mut destination_vault = destination_database.open_cached()?;
+    if !force && destination_vault.key_file.tokens.contains_key(new_name) {
+        Err(Error::TokenExists(new_name.to_owned()))?;
+    }
+
+    destination_vault.encrypt_token(new_name, &mut copy)?;
+    destination_vault
+        .key_file
+        .tokens
+        .insert(new_name.to_owned(), copy);
+    destination_vault.save()?;
+
+    let destination = destination_database.token_name(new_name);
+    if remove_source {
+        source_vault.key_file.tokens.remove(name);
+        source_vault.save()?;
+        println!("Token {} moved to {}", source, destination);
+    } else {
+        println!("Token {} copied to {}", source, destination);
+    }
+    Ok(())
+}
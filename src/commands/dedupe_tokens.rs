@@ -0,0 +1,116 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{
+    prompt::{ask_credentials, confirm},
+    vault::Vault,
+};
+
+/// Finds tokens with the same secret and parameters, optionally removes all but the first one
+/// (in name order) from every group
+pub fn dedupe_tokens(key_file: &Path, remove: bool, skip_confirmation: bool) -> Result<()> {
+    let mut vault = Vault::open(key_file, ask_credentials)?;
+
+    let duplicate_groups = duplicate_groups(vault.fingerprints()?);
+
+    if duplicate_groups.is_empty() {
+        println!("No duplicate tokens found");
+        return Ok(());
+    }
+
+    for names in duplicate_groups.iter() {
+        let token = &vault.key_file.tokens[&names[0]];
+        let title = format!("{} {}", token.issuer, token.label);
+        println!("{}: {}", title.trim(), names.join(", "));
+    }
+
+    if !remove {
+        println!(
+            "Found {} groups of duplicate tokens",
+            duplicate_groups.len()
+        );
+        return Ok(());
+    }
+
+    let redundant = duplicate_groups
+        .iter()
+        .flat_map(|names| names.iter().skip(1))
+        .collect::<Vec<_>>();
+
+    if !skip_confirmation
+        && !confirm(&format!(
+            "Remove {} duplicate tokens, keeping first token of every group?",
+            redundant.len()
+        ))?
+    {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    for name in redundant {
+        vault.key_file.tokens.remove(name);
+        println!("removed: {}", name);
+    }
+
+    vault.save()?;
+
+    println!("Database saved");
+    Ok(())
+}
+
+/// Names of tokens sharing fingerprint, groups are sorted by their first name and names within
+/// group are in name order
+fn duplicate_groups(fingerprints: BTreeMap<String, [u8; 32]>) -> Vec<Vec<String>> {
+    let mut groups: BTreeMap<[u8; 32], Vec<String>> = BTreeMap::new();
+    for (name, fingerprint) in fingerprints {
+        groups.entry(fingerprint).or_default().push(name);
+    }
+
+    let mut duplicate_groups = groups
+        .into_values()
+        .filter(|names| names.len() > 1)
+        .collect::<Vec<_>>();
+    duplicate_groups.sort();
+    duplicate_groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{types::Token, vault::token_fingerprint};
+
+    #[test]
+    fn duplicates_are_grouped() {
+        let token = Token {
+            digits: 6,
+            period: 30,
+            ..Default::default()
+        };
+        let fingerprints = [
+            ("github", token_fingerprint(&token, b"one", None)),
+            ("gitlab", token_fingerprint(&token, b"two", None)),
+            ("github-2", token_fingerprint(&token, b"one", None)),
+            (
+                "github-8",
+                token_fingerprint(
+                    &Token {
+                        digits: 8,
+                        ..token.clone()
+                    },
+                    b"one",
+                    None,
+                ),
+            ),
+            ("a-gitlab", token_fingerprint(&token, b"two", None)),
+        ]
+        .into_iter()
+        .map(|(name, fingerprint)| (name.to_owned(), fingerprint))
+        .collect();
+
+        assert_eq!(
+            duplicate_groups(fingerprints),
+            [vec!["a-gitlab", "gitlab"], vec!["github", "github-2"]]
+        );
+    }
+}
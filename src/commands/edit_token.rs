@@ -0,0 +1,130 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{
+    error::Error,
+    otp::{MAX_DIGITS, MIN_DIGITS},
+    prompt::ask_credentials,
+    types::TokenAlgorithm,
+    vault::Vault,
+};
+
+/// Token parameters to change, `None` (or empty list) leaves parameter as is
+pub struct TokenChanges {
+    pub issuer: Option<String>,
+    pub label: Option<String>,
+    pub digits: Option<u32>,
+    pub period: Option<u64>,
+    pub algorithm: Option<TokenAlgorithm>,
+    /// Empty group name removes token from group
+    pub group: Option<String>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub favorite: Option<bool>,
+    pub notes: Option<String>,
+    /// `Some(None)` clears sort order
+    pub sort_order: Option<Option<i64>>,
+}
+
+pub fn edit_token(key_file_path: &Path, token_name: &str, changes: TokenChanges) -> Result<()> {
+    let TokenChanges {
+        issuer,
+        label,
+        digits,
+        period,
+        algorithm,
+        group,
+        add_tags,
+        remove_tags,
+        favorite,
+        notes,
+        sort_order,
+    } = changes;
+
+    if issuer.is_none()
+        && label.is_none()
+        && digits.is_none()
+        && period.is_none()
+        && algorithm.is_none()
+        && group.is_none()
+        && add_tags.is_empty()
+        && remove_tags.is_empty()
+        && favorite.is_none()
+        && notes.is_none()
+        && sort_order.is_none()
+    {
+        Err(Error::InvalidArgument(
+            "Nothing to change, specify at least one parameter".to_owned(),
+        ))?;
+    }
+    if let Some(digits) = digits
+        && !(MIN_DIGITS..=MAX_DIGITS).contains(&digits)
+    {
+        Err(Error::InvalidArgument(format!(
+            "Token digits must be in range from {} to {}",
+            MIN_DIGITS, MAX_DIGITS
+        )))?;
+    }
+    if period == Some(0) {
+        Err(Error::InvalidArgument(
+            "Token period must be greater than zero".to_owned(),
+        ))?;
+    }
+
+    let mut vault = Vault::open(key_file_path, ask_credentials)?;
+
+    let token_type = vault
+        .key_file
+        .tokens
+        .get(token_name)
+        .ok_or_else(|| Error::TokenNotFound(token_name.to_owned()))?
+        .token_type;
+    if token_type.fixed_shape().is_some() && (digits.is_some() || period.is_some()) {
+        Err(Error::InvalidArgument(format!(
+            "Digits and period of {:?} tokens can't be changed",
+            token_type
+        )))?;
+    }
+
+    // Token key is re-encrypted since it is bound to code generation parameters
+    vault.update_token(token_name, |token| {
+        if let Some(issuer) = issuer {
+            token.issuer = issuer;
+        }
+        if let Some(label) = label {
+            token.label = label;
+        }
+        if let Some(digits) = digits {
+            token.digits = digits;
+        }
+        if let Some(period) = period {
+            token.period = period;
+        }
+        if let Some(algorithm) = algorithm {
+            token.algorithm = algorithm;
+        }
+        if let Some(group) = group {
+            token.group = Some(group.trim().to_owned()).filter(|group| !group.is_empty());
+        }
+        for tag in add_tags {
+            if !token.tags.contains(&tag) {
+                token.tags.push(tag);
+            }
+        }
+        token.tags.retain(|tag| !remove_tags.contains(tag));
+        if let Some(favorite) = favorite {
+            token.favorite = favorite;
+        }
+        if let Some(notes) = notes {
+            token.notes = notes;
+        }
+        if let Some(sort_order) = sort_order {
+            token.sort_order = sort_order;
+        }
+    })?;
+
+    vault.save()?;
+
+    println!("Token {} updated", token_name);
+    Ok(())
+}
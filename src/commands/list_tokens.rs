@@ -0,0 +1,67 @@
+use anyhow::Result;
+
+use crate::profiles::Database;
+
+/// Prints token names with metadata, token keys are not decrypted so password is not required
+/// (unless metadata is sealed). Tokens of several databases are listed in one table with names
+/// prefixed by profile
+pub fn list_tokens(databases: &[Database]) -> Result<()> {
+    let vaults = databases
+        .iter()
+        .map(|database| Ok((database, database.open_metadata()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let tokens = vaults
+        .iter()
+        .flat_map(|(database, vault)| {
+            vault
+                .key_file
+                .sorted_tokens()
+                .into_iter()
+                .map(|(name, token)| (database.token_name(name), token))
+        })
+        .collect::<Vec<_>>();
+
+    if tokens.is_empty() {
+        println!("No tokens in database");
+        return Ok(());
+    }
+
+    let name_width = column_width("NAME", tokens.iter().map(|(name, _)| name.as_str()));
+    let issuer_width = column_width("ISSUER", tokens.iter().map(|(_, t)| t.issuer.as_str()));
+    let label_width = column_width("LABEL", tokens.iter().map(|(_, t)| t.label.as_str()));
+    let group_width = column_width(
+        "GROUP",
+        tokens
+            .iter()
+            .map(|(_, t)| t.group.as_deref().unwrap_or_default()),
+    );
+
+    println!(
+        "  {:name_width$}  {:issuer_width$}  {:label_width$}  {:group_width$}  TYPE  TAGS",
+        "NAME", "ISSUER", "LABEL", "GROUP"
+    );
+
+    for (name, token) in tokens {
+        println!(
+            "{} {:name_width$}  {:issuer_width$}  {:label_width$}  {:group_width$}  {:4}  {}",
+            if token.favorite { '*' } else { ' ' },
+            name,
+            token.issuer,
+            token.label,
+            token.group.as_deref().unwrap_or_default(),
+            format!("{:?}", token.token_type),
+            token.tags.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn column_width<'a>(header: &str, values: impl Iterator<Item = &'a str>) -> usize {
+    values
+        .map(|value| value.chars().count())
+        .chain(std::iter::once(header.len()))
+        .max()
+        .unwrap_or_default()
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::agent;
+
+/// Makes agent forget master key of database, or keys of all databases if none is specified
+pub fn lock_database(key_file: Option<&Path>) -> Result<()> {
+    agent::forget_keys(key_file)?;
+
+    match key_file {
+        Some(_) => println!("Database locked"),
+        None => println!("All databases locked"),
+    }
+    Ok(())
+}
@@ -1,42 +1,52 @@
 use anyhow::Result;
-use std::io::{Write, stdin, stdout};
-use std::path::PathBuf;
-use termion::input::TermRead;
-
-use crate::{parsers::*, types::BackupType};
-
-pub fn parse_backup_file(backup_type: &BackupType, backup_file: &PathBuf) -> Result<()> {
-    let stdout = stdout();
-    let mut stdout = stdout.lock();
-    let stdin = stdin();
-    let mut stdin = stdin.lock();
+use std::path::Path;
+
+use crate::{cli::BackupType, parsers::*, prompt::read_password};
+
+pub fn parse_backup_file(
+    backup_type: &BackupType,
+    backup_file: &Path,
+    skip_invalid: bool,
+) -> Result<()> {
+    let parsed = read_backup(backup_type, backup_file, skip_invalid)?;
+
+    // Token keys are redacted by their `Debug` implementation
+    println!("Tokens:\n{:#?}", parsed.tokens);
+
+    for err in parsed.rejected.iter() {
+        println!("rejected: {}", err);
+    }
+    if !parsed.rejected.is_empty() {
+        println!("Rejected: {}", parsed.rejected.len());
+    }
+    Ok(())
+}
 
-    let tokens = match backup_type {
+/// Parses backup of given type, asks for backup password if backup is encrypted. Invalid entries
+/// of line based backups are skipped if `skip_invalid` is set
+pub fn read_backup(
+    backup_type: &BackupType,
+    backup_file: &Path,
+    skip_invalid: bool,
+) -> Result<ParsedBackup> {
+    let parsed = match backup_type {
         BackupType::TokensList => {
-            stdout.write_all(b"parsing tokens list...\n")?;
-            stdout.flush().unwrap();
-
-            parse_tokens_list(backup_file)
+            println!("parsing tokens list...");
+            parse_tokens_list(backup_file, skip_invalid)?
         }
         BackupType::FreeOtp => {
-            stdout.write_all(b"Enter backup file password: ")?;
-            stdout.flush().unwrap();
-            let input_password = stdin.read_passwd(&mut stdout)?.unwrap();
-            stdout.write_all(b"\nparsing backup...\n")?;
-            stdout.flush().unwrap();
-
-            parse_freeotp_backup(backup_file, input_password.as_str())
+            let input_password = read_password("Enter backup file password: ")?;
+            println!("parsing backup...");
+            ParsedBackup {
+                tokens: parse_freeotp_backup(backup_file, input_password.as_str())?,
+                rejected: Vec::new(),
+            }
         }
         BackupType::GoogleAuth => {
-            stdout.write_all(b"parsing backup...\n")?;
-            stdout.flush().unwrap();
-
-            parse_googleauth_backup(backup_file)
+            println!("parsing backup...");
+            parse_googleauth_backup(backup_file, skip_invalid)?
         }
-    }?;
+    };
 
-    std::mem::drop(stdout);
-
-    println!("Tokens:\n{:#?}", tokens);
-    Ok(())
+    Ok(parsed)
 }
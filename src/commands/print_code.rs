@@ -0,0 +1,87 @@
+use anyhow::Result;
+
+use crate::{
+    cli::TokenSelector,
+    error::Error,
+    otp,
+    profiles::{Database, find_token},
+    types::TokenType,
+};
+
+/// Prints current code once, without interactive screen, so it can be used in scripts. Counters
+/// of HOTP tokens are advanced and saved, unless code of other time or offset is requested. Tag
+/// selects tokens of all opened databases
+pub fn print_code(
+    databases: &[Database],
+    selector: &TokenSelector,
+    at: Option<u64>,
+    offset: Option<i64>,
+) -> Result<()> {
+    match selector {
+        TokenSelector::Name(name) => {
+            let (database, name) = find_token(databases, name)?;
+            print_database_codes(database, &TokenSelector::Name(name.to_owned()), at, offset)
+        }
+        TokenSelector::Tag(tag) => {
+            let mut found = false;
+            for database in databases {
+                match print_database_codes(database, selector, at, offset) {
+                    Ok(()) => found = true,
+                    Err(err)
+                        if matches!(err.downcast_ref::<Error>(), Some(Error::TagNotFound(_))) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            match found {
+                true => Ok(()),
+                false => Err(Error::TagNotFound(tag.to_owned()))?,
+            }
+        }
+    }
+}
+
+fn print_database_codes(
+    database: &Database,
+    selector: &TokenSelector,
+    at: Option<u64>,
+    offset: Option<i64>,
+) -> Result<()> {
+    let mut vault = database.open_cached()?;
+
+    let tokens = selector
+        .select(&vault.key_file)?
+        .into_iter()
+        .map(|(name, token)| (name.to_owned(), token.token_type))
+        .collect::<Vec<_>>();
+    let preview = at.is_some() || offset.is_some();
+
+    for (name, _) in &tokens {
+        let code = match preview {
+            true => {
+                let time = match at {
+                    Some(time) => time,
+                    None => otp::now()?,
+                };
+                vault
+                    .generator(name, &vault.key_file.tokens[name])?
+                    .generate_with_offset(time, offset.unwrap_or(0))?
+            }
+            false => vault.generate_code(name)?,
+        };
+        match selector {
+            TokenSelector::Name(_) => println!("{}", code),
+            TokenSelector::Tag(_) => println!("{}: {}", database.token_name(name), code),
+        }
+    }
+
+    if !preview
+        && tokens
+            .iter()
+            .any(|(_, token_type)| *token_type == TokenType::Hotp)
+    {
+        vault.save()?;
+    }
+
+    Ok(())
+}
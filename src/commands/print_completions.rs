@@ -0,0 +1,9 @@
+use anyhow::Result;
+use std::io::stdout;
+
+use crate::completions::{Shell, write_registration};
+
+/// Prints completion script for shell, it should be sourced on shell startup
+pub fn print_completions(shell: Shell) -> Result<()> {
+    write_registration(shell, &mut stdout())
+}
@@ -0,0 +1,18 @@
+use anyhow::Result;
+use std::io::stdout;
+use std::path::Path;
+
+use crate::man::{write_page, write_pages};
+
+/// Prints man page of command to stdout, or writes pages of all commands to directory
+pub fn print_man(command: Option<&str>, out_dir: Option<&Path>) -> Result<()> {
+    match out_dir {
+        Some(out_dir) => {
+            for path in write_pages(out_dir)? {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
+        None => write_page(command, &mut stdout()),
+    }
+}
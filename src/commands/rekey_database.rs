@@ -0,0 +1,71 @@
+use anyhow::Result;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::{
+    challenge_response,
+    error::Error,
+    prompt::{ask_credentials, read_key_file, read_password},
+    types::{ChallengeResponse, UnlockFactors},
+    vault::{Credentials, Vault, random_bytes},
+};
+
+/// Length of generated key file contents
+const KEY_FILE_LENGTH: usize = 64;
+
+/// Changes database password and unlock factors, factors that are not specified are removed.
+/// Challenge-response uses helper command from command line or config file
+pub fn rekey_database(
+    key_file: &Path,
+    unlock_key_file: Option<&Path>,
+    challenge_response: bool,
+) -> Result<()> {
+    let mut vault = Vault::open(key_file, ask_credentials)?;
+
+    let password = read_password("Enter new database password: ")?;
+    if read_password("Repeat new database password: ")?.as_bytes() != password.as_bytes() {
+        Err(Error::CredentialsRequired(
+            "Passwords do not match".to_owned(),
+        ))?;
+    }
+
+    let key_file_contents = match unlock_key_file {
+        Some(path) if !path.exists() => {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)?
+                .write_all(&random_bytes(KEY_FILE_LENGTH))?;
+            println!("Key file created at {}, keep it safe", path.display());
+            Some(read_key_file(path)?)
+        }
+        Some(path) => Some(read_key_file(path)?),
+        None => None,
+    };
+
+    let challenge_response = challenge_response.then(ChallengeResponse::with_random_challenge);
+    let response = challenge_response
+        .as_ref()
+        .map(challenge_response::respond)
+        .transpose()?;
+
+    vault.rekey(
+        UnlockFactors {
+            key_file: key_file_contents.is_some(),
+            challenge_response,
+        },
+        &Credentials {
+            password,
+            key_file: key_file_contents,
+            challenge_response: response,
+        },
+    )?;
+
+    vault.save()?;
+
+    println!("Database re-encrypted");
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{
+    error::Error,
+    prompt::{ask_credentials, confirm},
+    vault::Vault,
+};
+
+pub fn remove_token(key_file_path: &Path, token_name: &str, skip_confirmation: bool) -> Result<()> {
+    let mut vault = Vault::open(key_file_path, ask_credentials)?;
+
+    let token = vault
+        .key_file
+        .tokens
+        .get(token_name)
+        .ok_or_else(|| Error::TokenNotFound(token_name.to_owned()))?;
+
+    if !skip_confirmation
+        && !confirm(&format!(
+            "Remove token {} ({} {})?",
+            token_name, token.issuer, token.label
+        ))?
+    {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    vault.key_file.tokens.remove(token_name);
+
+    vault.save()?;
+
+    println!("Token {} removed", token_name);
+    Ok(())
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{prompt::ask_credentials, vault::Vault};
+
+/// Renames token, requires password since token key is bound to token name
+pub fn rename_token(key_file_path: &Path, old_name: &str, new_name: &str) -> Result<()> {
+    let mut vault = Vault::open(key_file_path, ask_credentials)?;
+
+    vault.rename_token(old_name, new_name)?;
+
+    vault.save()?;
+
+    println!("Token {} renamed to {}", old_name, new_name);
+    Ok(())
+}
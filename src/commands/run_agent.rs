@@ -0,0 +1,9 @@
+use anyhow::Result;
+use std::time::Duration;
+
+use crate::agent;
+
+/// Runs agent that keeps master keys of unlocked databases, so password is not asked every time
+pub fn run_agent(lifetime: Duration) -> Result<()> {
+    agent::serve(lifetime)
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{prompt::ask_credentials, vault::Vault};
+
+/// Switches database between sealed mode, where all token metadata is encrypted, and plain mode,
+/// where only token keys are encrypted
+pub fn seal_database(key_file: &Path, sealed: bool) -> Result<()> {
+    let mut vault = Vault::open(key_file, ask_credentials)?;
+
+    if vault.is_sealed() == sealed {
+        match sealed {
+            true => println!("Database is already sealed"),
+            false => println!("Database is not sealed"),
+        }
+        return Ok(());
+    }
+
+    // Plain database does not verify password on open, so check it before sealing tokens under it
+    vault.fingerprints()?;
+
+    vault.set_sealed(sealed)?;
+    vault.save()?;
+
+    match sealed {
+        true => println!("Database sealed, token names and metadata are encrypted now"),
+        false => println!("Database unsealed, token names and metadata are stored in plain text"),
+    }
+    Ok(())
+}
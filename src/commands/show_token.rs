@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::{error::Error, prompt::ask_credentials, vault::Vault};
+
+/// Prints token metadata, secret is printed only on request since it requires database password
+pub fn show_token(key_file_path: &Path, token_name: &str, show_secret: bool) -> Result<()> {
+    let mut vault = Vault::open_metadata(key_file_path, ask_credentials)?;
+
+    let token = vault
+        .key_file
+        .tokens
+        .get(token_name)
+        .ok_or_else(|| Error::TokenNotFound(token_name.to_owned()))?;
+
+    println!("name: {}", token_name);
+    println!("issuer: {}", token.issuer);
+    println!("label: {}", token.label);
+    println!("type: {:?}", token.token_type);
+    println!("algorithm: {:?}", token.algorithm);
+    println!("digits: {}", token.digits);
+    println!("period: {}", token.period);
+    println!("t0: {}", token.t0);
+    println!("counter: {}", token.counter);
+    println!("group: {}", token.group.as_deref().unwrap_or_default());
+    println!("tags: {}", token.tags.join(", "));
+    println!("favorite: {}", token.favorite);
+    println!(
+        "sort order: {}",
+        token
+            .sort_order
+            .map(|order| order.to_string())
+            .unwrap_or_default()
+    );
+    println!("notes: {}", token.notes);
+
+    if show_secret {
+        // Sealed database is already unlocked
+        if !vault.is_sealed() {
+            vault = Vault::open(key_file_path, ask_credentials)?;
+        }
+        let token = &vault.key_file.tokens[token_name];
+        let secret = vault.decrypt_secret(token_name, token)?;
+        let encoded = Zeroizing::new(base32::encode(
+            base32::Alphabet::Rfc4648 { padding: false },
+            &secret,
+        ));
+
+        println!("secret: {}", encoded.as_str());
+    }
+
+    Ok(())
+}
@@ -1,25 +1,66 @@
-use anyhow::{Result, anyhow};
-use argon2::Argon2;
-use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce, aead::Aead};
-use cli_clipboard::{ClipboardContext, ClipboardProvider};
-use std::collections::BTreeMap;
-use std::fs;
+use anyhow::Result;
 use std::io::{Write, stdin, stdout};
-use std::path::PathBuf;
 use std::process;
 use std::sync::mpsc::channel;
-use std::{thread, time};
+use std::thread;
 use termion::{clear, cursor, event::Key, input::TermRead, raw::IntoRawMode};
-use toml;
-use totp_rs::{Algorithm, TOTP};
 
-use crate::types::{BufferedStdout, KeyFile, TokenAlgorithm, TokenType};
+use crate::{
+    buffered_stdout::BufferedStdout,
+    clipboard::Clipboard,
+    clock::{TIMEOUT, drift_warning, query_server},
+    code_view::CodeView,
+    config::Config,
+    error::Error,
+    otp::Generator,
+    profiles::Database,
+    prompt::take_prompted_lines,
+    types::TokenType,
+    vault::Vault,
+};
+
+/// Token list where token is picked to show its code, tokens of several databases are shown
+/// with names prefixed by profile
+pub fn start_interactive(databases: &[Database], config: &Config) -> Result<()> {
+    // Databases stay open to save counters of HOTP tokens
+    let mut vaults = databases
+        .iter()
+        .map(Database::open_cached)
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut all_tokens = Vec::new();
+
+    for (index, (database, vault)) in databases.iter().zip(&vaults).enumerate() {
+        for (token_name, token) in vault.key_file.sorted_tokens() {
+            all_tokens.push(TokenEntry {
+                database: index,
+                name: token_name.to_owned(),
+                display_name: database.token_name(token_name),
+                group: token.group.clone(),
+                generator: vault.generator(token_name, token)?,
+            });
+        }
+    }
 
-const PAGE_SIZE: usize = 5;
+    let profiles = databases
+        .iter()
+        .filter_map(|database| database.profile.clone())
+        .collect::<Vec<_>>();
+
+    let mut groups = all_tokens
+        .iter()
+        .filter_map(|entry| entry.group.clone())
+        .collect::<Vec<_>>();
+    groups.sort();
+    groups.dedup();
 
-pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
-    let mut maybe_clipboard = ClipboardContext::new().ok();
-    let stdout_handle = stdout().into_raw_mode()?;
+    let mut group_filter: Option<usize> = None;
+    let mut profile_filter: Option<usize> = None;
+    let mut tokens = filter_tokens(&all_tokens, None, None);
+
+    let mut clipboard = Clipboard::new();
+    let mut clipboard_error = None;
+    let stdout_handle = stdout().into_raw_mode().map_err(Error::Terminal)?;
 
     let clean_exit = || -> ! {
         std::mem::drop(stdout_handle);
@@ -27,59 +68,18 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
     };
 
     let mut stdout = stdout();
-    let mut stdin = stdin();
-
-    write!(stdout, "Enter database password: ")?;
-    stdout.flush()?;
-    let key_file_password = stdin.read_passwd(&mut stdout)?.unwrap_or_default();
-    write!(stdout, "\r\n{}{}", cursor::Up(1), clear::AfterCursor)?;
-    stdout.flush()?;
-
-    let key_file_data = fs::read_to_string(key_file)?;
-    let key_file = toml::from_str::<KeyFile>(key_file_data.as_str())?;
-
-    let mut encryption_key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(
-            key_file_password.as_bytes(),
-            &key_file.master_key_salt,
-            &mut encryption_key,
-        )
-        .map_err(|err| anyhow!("{}", err))?;
-
-    let cipher = XChaCha20Poly1305::new_from_slice(encryption_key.as_slice())?;
-
-    let mut tokens = BTreeMap::new();
-
-    for (token_name, token) in key_file.tokens.iter() {
-        let decrypted_token_key = cipher
-            .decrypt(XNonce::from_slice(&token.key[0..24]), &token.key[24..])
-            .map_err(|err| anyhow!("Failed to decrypt token key: {}", err))?;
-
-        let algorithm = match token.algorithm {
-            TokenAlgorithm::Sha1 => Algorithm::SHA1,
-            TokenAlgorithm::Sha256 => Algorithm::SHA256,
-            TokenAlgorithm::Sha512 => Algorithm::SHA512,
-        };
-
-        if token.token_type != TokenType::Totp {
-            Err(anyhow!(
-                "This token type is not supported yet: {:?}",
-                token.token_type
-            ))?;
-        }
-
-        let totp = TOTP::new_unchecked(
-            algorithm,
-            token.digits as usize,
-            1,
-            token.period,
-            decrypted_token_key.clone(),
-            Some(token.issuer.clone()),
-            token.label.clone(),
-        );
-
-        tokens.insert(token_name.to_owned(), totp);
+    let stdin = stdin();
+
+    // Erase password and unlock factor prompts
+    let prompted_lines = take_prompted_lines();
+    if prompted_lines > 0 {
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Up(prompted_lines),
+            clear::AfterCursor
+        )?;
+        stdout.flush()?;
     }
 
     let mut buffered_stdout = BufferedStdout::new(stdout);
@@ -98,17 +98,40 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
         }
     });
 
+    if config.interface.clock_check {
+        let sender_clock = sender.clone();
+        let server = config.interface.clock_server.clone();
+        thread::spawn(move || {
+            // Server may be unreachable, codes are shown anyway
+            if let Ok(drift) = query_server(&server, TIMEOUT)
+                && let Some(warning) = drift_warning(drift)
+            {
+                sender_clock.send(AppEvent::ClockWarning(warning)).ok();
+            }
+        });
+    }
+
+    let refresh_interval = config.interface.refresh_interval;
     thread::spawn(move || {
         loop {
             sender.send(AppEvent::Timer).unwrap();
-            thread::sleep(time::Duration::from_millis(1000));
+            thread::sleep(refresh_interval);
         }
     });
 
     let mut name_buf = String::new();
     let mut render_mode = RenderMode::TokensList { page: 0 };
 
-    render_token_list(&tokens, &mut buffered_stdout, &name_buf, 0)?;
+    let mut view_state = ViewState {
+        groups: &groups,
+        group_filter,
+        profiles: &profiles,
+        profile_filter,
+        clock_warning: None,
+        config,
+    };
+
+    render_token_list(&tokens, &view_state, &mut buffered_stdout, &name_buf, 0)?;
 
     loop {
         let event = match receiver.recv() {
@@ -125,9 +148,40 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
                 clean_exit()
             }
             AppEvent::Timer => {
-                if let RenderMode::Token { ref name, token } = render_mode {
-                    render_token(name.as_str(), token, &mut buffered_stdout)?;
-                } else {
+                if let RenderMode::Token {
+                    ref name,
+                    ref generator,
+                } = render_mode
+                {
+                    render_token(
+                        name.as_str(),
+                        generator,
+                        clipboard_error.as_ref(),
+                        &view_state,
+                        &mut buffered_stdout,
+                    )?;
+                }
+            }
+            AppEvent::ClockWarning(warning) => {
+                view_state.clock_warning = Some(warning);
+                match render_mode {
+                    RenderMode::TokensList { page } => render_token_list(
+                        &tokens,
+                        &view_state,
+                        &mut buffered_stdout,
+                        &name_buf,
+                        page,
+                    )?,
+                    RenderMode::Token {
+                        ref name,
+                        ref generator,
+                    } => render_token(
+                        name.as_str(),
+                        generator,
+                        clipboard_error.as_ref(),
+                        &view_state,
+                        &mut buffered_stdout,
+                    )?,
                 }
             }
             AppEvent::Key(key) => match render_mode {
@@ -137,31 +191,79 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
                             buffered_stdout.clear().ok();
                             clean_exit()
                         }
-                        Key::Delete | Key::Ctrl('d') => {
+                        key if key == Key::Delete || config.keys.clear.matches(&key) => {
                             name_buf = String::new();
-                            render_token_list(&tokens, &mut buffered_stdout, &name_buf, page)?;
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                page,
+                            )?;
                         }
-                        Key::Left => {
-                            if page != 0 {
-                                render_mode = RenderMode::TokensList { page: page - 1 };
-                                render_token_list(
-                                    &tokens,
-                                    &mut buffered_stdout,
-                                    &name_buf,
-                                    page - 1,
-                                )?;
-                            }
+                        Key::Left if page != 0 => {
+                            render_mode = RenderMode::TokensList { page: page - 1 };
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                page - 1,
+                            )?;
                         }
-                        Key::Right => {
-                            if page < tokens.len().div_ceil(PAGE_SIZE) - 1 {
-                                render_mode = RenderMode::TokensList { page: page + 1 };
-                                render_token_list(
-                                    &tokens,
-                                    &mut buffered_stdout,
-                                    &name_buf,
-                                    page + 1,
-                                )?;
-                            }
+                        Key::Right
+                            if page + 1 < tokens.len().div_ceil(config.interface.page_size) =>
+                        {
+                            render_mode = RenderMode::TokensList { page: page + 1 };
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                page + 1,
+                            )?;
+                        }
+                        key if config.keys.next_group.matches(&key) && !groups.is_empty() => {
+                            group_filter = match group_filter {
+                                None => Some(0),
+                                Some(index) if index + 1 < groups.len() => Some(index + 1),
+                                Some(_) => None,
+                            };
+                            view_state.group_filter = group_filter;
+                            tokens = filter_tokens(
+                                &all_tokens,
+                                group_filter.map(|index| groups[index].as_str()),
+                                profile_filter,
+                            );
+                            render_mode = RenderMode::TokensList { page: 0 };
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                0,
+                            )?;
+                        }
+                        key if config.keys.next_profile.matches(&key) && !profiles.is_empty() => {
+                            profile_filter = match profile_filter {
+                                None => Some(0),
+                                Some(index) if index + 1 < profiles.len() => Some(index + 1),
+                                Some(_) => None,
+                            };
+                            view_state.profile_filter = profile_filter;
+                            tokens = filter_tokens(
+                                &all_tokens,
+                                group_filter.map(|index| groups[index].as_str()),
+                                profile_filter,
+                            );
+                            render_mode = RenderMode::TokensList { page: 0 };
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                0,
+                            )?;
                         }
                         Key::Char('\n') => {
                             if name_buf.is_empty() {
@@ -171,51 +273,102 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
                             if let Ok(index) = name_buf.parse::<usize>()
                                 && index < tokens.len()
                             {
-                                let token_name = tokens.keys().skip(index).next().unwrap().clone();
-                                let token = tokens.get(token_name.as_str()).unwrap();
+                                let entry = tokens[index];
+                                let generator = open_token(&mut vaults[entry.database], entry)?;
                                 name_buf = String::new();
-                                render_token(token_name.as_str(), token, &mut buffered_stdout)?;
+                                clipboard_error = None;
+                                render_token(
+                                    entry.display_name.as_str(),
+                                    &generator,
+                                    None,
+                                    &view_state,
+                                    &mut buffered_stdout,
+                                )?;
 
                                 render_mode = RenderMode::Token {
-                                    name: token_name,
-                                    token,
+                                    name: entry.display_name.clone(),
+                                    generator,
                                 };
-                            } else if let Some(token) = tokens.get(name_buf.as_str()) {
-                                render_token(name_buf.as_str(), token, &mut buffered_stdout)?;
+                            } else if let Some(entry) = all_tokens
+                                .iter()
+                                .find(|entry| entry.display_name == name_buf)
+                            {
+                                let generator = open_token(&mut vaults[entry.database], entry)?;
+                                clipboard_error = None;
+                                render_token(
+                                    name_buf.as_str(),
+                                    &generator,
+                                    None,
+                                    &view_state,
+                                    &mut buffered_stdout,
+                                )?;
 
                                 render_mode = RenderMode::Token {
                                     name: std::mem::take(&mut name_buf),
-                                    token,
+                                    generator,
                                 };
                             } else {
-                                render_token_list(&tokens, &mut buffered_stdout, &name_buf, page)?;
+                                render_token_list(
+                                    &tokens,
+                                    &view_state,
+                                    &mut buffered_stdout,
+                                    &name_buf,
+                                    page,
+                                )?;
                             }
                         }
                         Key::Backspace => {
                             name_buf.pop();
-                            render_token_list(&tokens, &mut buffered_stdout, &name_buf, page)?;
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                page,
+                            )?;
                         }
                         Key::Char(ch) => {
                             name_buf.push(ch);
-                            render_token_list(&tokens, &mut buffered_stdout, &name_buf, page)?;
+                            render_token_list(
+                                &tokens,
+                                &view_state,
+                                &mut buffered_stdout,
+                                &name_buf,
+                                page,
+                            )?;
                         }
                         _ => {}
                     };
                 }
-                RenderMode::Token { name: _, token } => match key {
+                RenderMode::Token {
+                    ref name,
+                    ref generator,
+                } => match key {
                     Key::Esc | Key::Ctrl('c') => {
                         buffered_stdout.clear().ok();
                         clean_exit()
                     }
-                    Key::Char('c') => {
-                        if let Some(clipboard) = maybe_clipboard.as_mut() {
-                            let code = token.generate_current()?;
-                            clipboard.set_contents(code).ok();
-                        }
+                    key if config.keys.copy.matches(&key) => {
+                        let code = CodeView::new(generator, config.interface.next_code_threshold)?
+                            .code_to_copy(config.clipboard.copy_next);
+                        clipboard_error = clipboard.copy(code).err();
+                        render_token(
+                            name.as_str(),
+                            generator,
+                            clipboard_error.as_ref(),
+                            &view_state,
+                            &mut buffered_stdout,
+                        )?;
                     }
-                    Key::Char('q') => {
+                    key if config.keys.back.matches(&key) => {
                         render_mode = RenderMode::TokensList { page: 0 };
-                        render_token_list(&tokens, &mut buffered_stdout, &name_buf, 0)?;
+                        render_token_list(
+                            &tokens,
+                            &view_state,
+                            &mut buffered_stdout,
+                            &name_buf,
+                            0,
+                        )?;
                     }
                     _ => {}
                 },
@@ -224,32 +377,72 @@ pub fn start_interactive(key_file: &PathBuf) -> Result<()> {
     }
 }
 
+/// Generator for token view, code of HOTP token is considered used once it is shown, so its
+/// counter is advanced and saved
+fn open_token(vault: &mut Vault, entry: &TokenEntry) -> Result<Generator> {
+    let token = &vault.key_file.tokens[&entry.name];
+    if token.token_type != TokenType::Hotp {
+        return Ok(entry.generator.clone());
+    }
+
+    let generator = vault.generator(&entry.name, token)?;
+    vault.advance_counter(&entry.name)?;
+    vault.save()?;
+    Ok(generator)
+}
+
+/// Tokens of selected group and database (all tokens if none selected) keeping display order
+fn filter_tokens<'a>(
+    all_tokens: &'a [TokenEntry],
+    group: Option<&str>,
+    database: Option<usize>,
+) -> Vec<&'a TokenEntry> {
+    all_tokens
+        .iter()
+        .filter(|entry| group.is_none() || entry.group.as_deref() == group)
+        .filter(|entry| database.is_none() || Some(entry.database) == database)
+        .collect()
+}
+
 fn render_token_list(
-    tokens: &BTreeMap<String, TOTP>,
+    tokens: &[&TokenEntry],
+    view_state: &ViewState,
     buffered_stdout: &mut BufferedStdout,
     name_buffer: &str,
     page: usize,
 ) -> Result<()> {
-    let paging_required = PAGE_SIZE < tokens.len();
+    let config = view_state.config;
+    let page_size = config.interface.page_size;
+    let paging_required = page_size < tokens.len();
+
+    render_clock_warning(view_state, buffered_stdout);
+
+    if let Some(index) = view_state.profile_filter {
+        buffered_stdout.add(&format!("Profile: {}\r\n", view_state.profiles[index]));
+    }
+
+    if let Some(index) = view_state.group_filter {
+        buffered_stdout.add(&format!("Group: {}\r\n", view_state.groups[index]));
+    }
 
     if paging_required {
         buffered_stdout.add(&format!(
             "Total tokens amount: {}, showing page {} of {}\r\n\r\n",
             tokens.len(),
             page + 1,
-            tokens.len().div_ceil(PAGE_SIZE)
+            tokens.len().div_ceil(page_size)
         ));
     } else {
         buffered_stdout.add(&format!("Total tokens amount: {}\r\n\r\n", tokens.len(),));
     }
 
-    for (index, (token_name, _)) in tokens
+    for (index, entry) in tokens
         .iter()
         .enumerate()
-        .skip(page * PAGE_SIZE)
-        .take(PAGE_SIZE)
+        .skip(page * page_size)
+        .take(page_size)
     {
-        buffered_stdout.add(&format!("   [{index}] {token_name}\r\n"));
+        buffered_stdout.add(&format!("   [{index}] {}\r\n", entry.display_name));
     }
 
     buffered_stdout.add("\r\n");
@@ -258,8 +451,24 @@ fn render_token_list(
         buffered_stdout.add("use left and right arrows to navigate between pages,\r\n");
     }
 
-    buffered_stdout
-        .add("press 'Ctrl+c' or 'Esc' to exit,\r\npress 'Ctrl+d' or 'Del' to clear input.\r\n");
+    if !view_state.groups.is_empty() {
+        buffered_stdout.add(&format!(
+            "press '{}' to switch between groups,\r\n",
+            config.keys.next_group.name
+        ));
+    }
+
+    if !view_state.profiles.is_empty() {
+        buffered_stdout.add(&format!(
+            "press '{}' to switch between databases,\r\n",
+            config.keys.next_profile.name
+        ));
+    }
+
+    buffered_stdout.add(&format!(
+        "press 'Ctrl+c' or 'Esc' to exit,\r\npress '{}' or 'Del' to clear input.\r\n",
+        config.keys.clear.name
+    ));
 
     buffered_stdout.add(&format!(
         "Enter token index or name to show code: {}\r\n",
@@ -272,32 +481,93 @@ fn render_token_list(
     Ok(())
 }
 
-fn render_token(name: &str, token: &TOTP, buffered_stdout: &mut BufferedStdout) -> Result<()> {
-    let code = token.generate_current()?;
-    let ttl = token.ttl()?;
+fn render_token(
+    name: &str,
+    generator: &Generator,
+    clipboard_error: Option<&Error>,
+    view_state: &ViewState,
+    buffered_stdout: &mut BufferedStdout,
+) -> Result<()> {
+    let config = view_state.config;
+    let code_view = CodeView::new(generator, config.interface.next_code_threshold)?;
+
+    render_clock_warning(view_state, buffered_stdout);
 
     buffered_stdout.add(&format!(
-        "token: {}\r\ncode: {} ttl: {}\r\n\r\n",
-        name, code, ttl
+        "token: {}\r\ncode: {}\r\n\r\n",
+        name,
+        config.theme.code.paint(&code_view.to_string())
     ));
 
-    buffered_stdout.add("press 'c' to copy code to clipboard,\r\n");
-    buffered_stdout.add("press 'q' to return to token list,\r\n");
+    buffered_stdout.add(&format!(
+        "press '{}' to copy code to clipboard,\r\n",
+        config.keys.copy.name
+    ));
+    buffered_stdout.add(&format!(
+        "press '{}' to return to token list,\r\n",
+        config.keys.back.name
+    ));
     buffered_stdout.add("press 'Ctrl+c' or 'Esc' to exit\r\n");
 
+    if let Some(err) = clipboard_error {
+        buffered_stdout.add(&format!(
+            "\r\n{}\r\n",
+            config.theme.error.paint(&err.to_string())
+        ));
+    }
+
     buffered_stdout.clear()?;
     buffered_stdout.flush()?;
 
     Ok(())
 }
 
+fn render_clock_warning(view_state: &ViewState, buffered_stdout: &mut BufferedStdout) {
+    if let Some(warning) = &view_state.clock_warning {
+        buffered_stdout.add(&format!(
+            "{}\r\n\r\n",
+            view_state
+                .config
+                .theme
+                .warning
+                .paint(&format!("Warning: {}", warning))
+        ));
+    }
+}
+
+struct TokenEntry {
+    /// Index of database token is stored in
+    database: usize,
+    /// Name in database
+    name: String,
+    /// Name prefixed with profile when several databases are opened
+    display_name: String,
+    group: Option<String>,
+    generator: Generator,
+}
+
+/// State shared by token list and token views
+struct ViewState<'a> {
+    groups: &'a [String],
+    /// Index of group which tokens are shown, all tokens are shown if not set
+    group_filter: Option<usize>,
+    /// Profiles of opened databases, empty if single database is opened
+    profiles: &'a [String],
+    /// Index of database which tokens are shown, tokens of all databases are shown if not set
+    profile_filter: Option<usize>,
+    /// Shown when system clock drift breaks time based codes
+    clock_warning: Option<String>,
+    config: &'a Config,
+}
+
 enum AppEvent {
     Timer,
     Key(Key),
+    ClockWarning(String),
     Terminate,
 }
 
-enum RenderMode<'a> {
+enum RenderMode {
     TokensList { page: usize },
-    Token { name: String, token: &'a TOTP },
+    Token { name: String, generator: Generator },
 }
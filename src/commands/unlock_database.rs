@@ -0,0 +1,15 @@
+use anyhow::Result;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{agent, prompt::ask_credentials, vault::Vault};
+
+/// Unlocks database and hands its master key to running agent
+pub fn unlock_database(key_file: &Path, lifetime: Option<Duration>) -> Result<()> {
+    let vault = Vault::open(key_file, ask_credentials)?;
+
+    agent::store_key(&vault, key_file, lifetime)?;
+
+    println!("Database unlocked");
+    Ok(())
+}
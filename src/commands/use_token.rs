@@ -1,22 +1,55 @@
-use anyhow::{Result, anyhow};
-use argon2::Argon2;
-use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce, aead::Aead};
-use cli_clipboard::{ClipboardContext, ClipboardProvider};
-use std::fs;
+use anyhow::Result;
 use std::io::{Write, stdin, stdout};
-use std::path::PathBuf;
+use std::path::Path;
 use std::process;
 use std::sync::mpsc::channel;
-use std::{thread, time};
+use std::thread;
 use termion::{clear, cursor, event::Key, input::TermRead, raw::IntoRawMode};
-use toml;
-use totp_rs::{Algorithm, TOTP};
 
-use crate::types::{BufferedStdout, KeyFile, TokenAlgorithm, TokenType};
+use crate::{
+    agent::cached_vault,
+    buffered_stdout::BufferedStdout,
+    cli::TokenSelector,
+    clipboard::Clipboard,
+    code_view::CodeView,
+    config::Config,
+    error::Error,
+    prompt::{ask_credentials, take_prompted_lines},
+    types::TokenType,
+    vault::Vault,
+};
+
+/// Shows codes of selected tokens until exit
+pub fn use_token(key_file: &Path, selector: &TokenSelector, config: &Config) -> Result<()> {
+    let mut vault = match cached_vault(key_file) {
+        Some(vault) => vault,
+        None => Vault::open(key_file, ask_credentials)?,
+    };
+
+    let tokens = selector
+        .select(&vault.key_file)?
+        .into_iter()
+        .map(|(name, token)| Ok((name.to_owned(), vault.generator(name, token)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Shown codes of HOTP tokens are considered used
+    let counter_based = tokens
+        .iter()
+        .map(|(name, _)| name.to_owned())
+        .filter(|name| vault.key_file.tokens[name].token_type == TokenType::Hotp)
+        .collect::<Vec<_>>();
+    if !counter_based.is_empty() {
+        for name in counter_based {
+            vault.advance_counter(&name)?;
+        }
+        vault.save()?;
+    }
+
+    std::mem::drop(vault);
 
-pub fn use_token(key_file: &PathBuf, token_label: &str) -> Result<()> {
-    let mut maybe_clipboard = ClipboardContext::new().ok();
-    let stdout_handle = stdout().into_raw_mode()?;
+    let mut clipboard = Clipboard::new();
+    let mut clipboard_error = None;
+    let stdout_handle = stdout().into_raw_mode().map_err(Error::Terminal)?;
 
     let clean_exit = || -> ! {
         std::mem::drop(stdout_handle);
@@ -24,75 +57,40 @@ pub fn use_token(key_file: &PathBuf, token_label: &str) -> Result<()> {
     };
 
     let mut stdout = stdout();
-    let mut stdin = stdin();
-
-    write!(stdout, "Enter database password: ")?;
-    stdout.flush()?;
-    let key_file_password = stdin.read_passwd(&mut stdout)?.unwrap_or_default();
-    write!(stdout, "\r\n{}{}", cursor::Up(1), clear::AfterCursor)?;
-    stdout.flush()?;
-
-    let key_file_data = fs::read_to_string(key_file)?;
-    let key_file = toml::from_str::<KeyFile>(key_file_data.as_str())?;
-
-    let token = key_file
-        .tokens
-        .get(token_label)
-        .ok_or(anyhow!("Token {} not found in file", token_label))?;
-
-    let mut encryption_key = [0u8; 32];
-    Argon2::default()
-        .hash_password_into(
-            key_file_password.as_bytes(),
-            &key_file.master_key_salt,
-            &mut encryption_key,
-        )
-        .map_err(|err| anyhow!("{}", err))?;
-
-    let cipher = XChaCha20Poly1305::new_from_slice(encryption_key.as_slice())?;
-
-    let decrypted_token_key = cipher
-        .decrypt(XNonce::from_slice(&token.key[0..24]), &token.key[24..])
-        .map_err(|err| anyhow!("Failed to decrypt token key: {}", err))?;
-
-    let algorithm = match token.algorithm {
-        TokenAlgorithm::Sha1 => Algorithm::SHA1,
-        TokenAlgorithm::Sha256 => Algorithm::SHA256,
-        TokenAlgorithm::Sha512 => Algorithm::SHA512,
-    };
-
-    if token.token_type != TokenType::Totp {
-        Err(anyhow!(
-            "This token type is not supported yet: {:?}",
-            token.token_type
-        ))?;
+    let stdin = stdin();
+
+    // Erase password and unlock factor prompts
+    let prompted_lines = take_prompted_lines();
+    if prompted_lines > 0 {
+        write!(
+            stdout,
+            "{}{}",
+            cursor::Up(prompted_lines),
+            clear::AfterCursor
+        )?;
+        stdout.flush()?;
     }
 
-    let totp = TOTP::new_unchecked(
-        algorithm,
-        token.digits as usize,
-        1,
-        token.period,
-        decrypted_token_key.clone(),
-        Some(token.issuer.clone()),
-        token.label.clone(),
-    );
-
     let mut buffered_stdout = BufferedStdout::new(stdout);
 
     let (sender, receiver) = channel::<AppEvent>();
     let sender_key = sender.clone();
 
+    let (back_key, copy_key) = (config.keys.back.clone(), config.keys.copy.clone());
     thread::spawn(move || {
         let stdin = stdin.lock();
 
         for k in stdin.keys() {
             match k {
                 Ok(key) => match key {
-                    Key::Char('q') | Key::Esc | Key::Ctrl('c') => {
-                        sender_key.send(AppEvent::Terminate).unwrap()
+                    Key::Esc | Key::Ctrl('c') => sender_key.send(AppEvent::Terminate).unwrap(),
+                    key if back_key.matches(&key) => sender_key.send(AppEvent::Terminate).unwrap(),
+                    key if copy_key.matches(&key) => {
+                        sender_key.send(AppEvent::CopyToClipboard(0)).unwrap()
                     }
-                    Key::Char('c') => sender_key.send(AppEvent::CopyToClipboard).unwrap(),
+                    Key::Char(ch) if ch.is_ascii_digit() => sender_key
+                        .send(AppEvent::CopyToClipboard(ch as usize - '0' as usize))
+                        .unwrap(),
                     _ => {}
                 },
                 Err(_) => sender_key.send(AppEvent::Terminate).unwrap(),
@@ -100,10 +98,11 @@ pub fn use_token(key_file: &PathBuf, token_label: &str) -> Result<()> {
         }
     });
 
+    let refresh_interval = config.interface.refresh_interval;
     thread::spawn(move || {
         loop {
             sender.send(AppEvent::Timer).unwrap();
-            thread::sleep(time::Duration::from_millis(1000));
+            thread::sleep(refresh_interval);
         }
     });
 
@@ -121,22 +120,51 @@ pub fn use_token(key_file: &PathBuf, token_label: &str) -> Result<()> {
                 buffered_stdout.clear().ok();
                 clean_exit()
             }
-            AppEvent::CopyToClipboard => {
-                if let Some(clipboard) = maybe_clipboard.as_mut() {
-                    let code = totp.generate_current()?;
-                    clipboard.set_contents(code).ok();
+            AppEvent::CopyToClipboard(index) => {
+                if let Some((_, generator)) = tokens.get(index) {
+                    let code = CodeView::new(generator, config.interface.next_code_threshold)?
+                        .code_to_copy(config.clipboard.copy_next);
+                    clipboard_error = clipboard.copy(code).err();
                 }
             }
             AppEvent::Timer => {
-                let code = totp.generate_current()?;
-                let ttl = totp.ttl()?;
-
+                if let [(token_label, generator)] = tokens.as_slice() {
+                    buffered_stdout.add(&format!(
+                        "token: {}\r\ncode: {}\r\n\r\n",
+                        token_label,
+                        config.theme.code.paint(
+                            &CodeView::new(generator, config.interface.next_code_threshold)?
+                                .to_string()
+                        )
+                    ));
+                    buffered_stdout.add(&format!(
+                        "press '{}' to copy code to clipboard,\r\n",
+                        config.keys.copy.name
+                    ));
+                } else {
+                    for (index, (token_label, generator)) in tokens.iter().enumerate() {
+                        buffered_stdout.add(&format!(
+                            "   [{}] {}: {}\r\n",
+                            index,
+                            token_label,
+                            config.theme.code.paint(
+                                &CodeView::new(generator, config.interface.next_code_threshold)?
+                                    .to_string()
+                            )
+                        ));
+                    }
+                    buffered_stdout.add("\r\npress token index to copy its code to clipboard,\r\n");
+                }
                 buffered_stdout.add(&format!(
-                    "token: {}\r\ncode: {} ttl: {}\r\n\r\n",
-                    token_label, code, ttl
+                    "press '{}', 'Ctrl+c' or 'Esc' to exit\r\n",
+                    config.keys.back.name
                 ));
-                buffered_stdout.add("press 'c' to copy code to clipboard,\r\n");
-                buffered_stdout.add("press 'q', 'Ctrl+c' or 'Esc' to exit\r\n");
+                if let Some(err) = &clipboard_error {
+                    buffered_stdout.add(&format!(
+                        "\r\n{}\r\n",
+                        config.theme.error.paint(&err.to_string())
+                    ));
+                }
                 buffered_stdout.clear()?;
                 buffered_stdout.flush()?;
             }
@@ -146,6 +174,7 @@ pub fn use_token(key_file: &PathBuf, token_label: &str) -> Result<()> {
 
 enum AppEvent {
     Terminate,
-    CopyToClipboard,
+    /// Holds index of token which code should be copied
+    CopyToClipboard(usize),
     Timer,
 }
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::{agent::open_vault, error::Error, types::TokenType};
+
+/// Checks code (e.g. one seen by colleague) against token and reports at which time step offset
+/// or HOTP counter it is valid, HOTP counter is not changed
+pub fn verify_token(key_file: &Path, token_name: &str, code: &str, window: u32) -> Result<()> {
+    let vault = open_vault(key_file)?;
+
+    let token = vault
+        .key_file
+        .tokens
+        .get(token_name)
+        .ok_or_else(|| Error::TokenNotFound(token_name.to_owned()))?;
+
+    match vault
+        .generator(token_name, token)?
+        .verify_current(code, window)?
+    {
+        Some(matched) if token.token_type == TokenType::Hotp => println!(
+            "Code matches counter {} (offset {:+} from stored counter)",
+            matched.counter, matched.offset
+        ),
+        Some(matched) => println!("Code matches, time step offset: {:+}", matched.offset),
+        None => Err(Error::CodeMismatch(token_name.to_owned()))?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::{CompleteEnv, CompletionCandidate, env::Shells};
+use std::env;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::{cli::Cli, config::Config, error::Error, profiles::Database, types::KeyFile};
+
+/// Environment variable set by completion script when it asks for candidates
+const COMPLETE_ENV: &str = "COMPLETE";
+
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Answers request of completion script and exits, does nothing on regular run
+pub fn complete() {
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_ENV)
+        .complete();
+}
+
+/// Writes script that registers completions in shell, script calls this binary for candidates
+pub fn write_registration(shell: Shell, buf: &mut dyn Write) -> Result<()> {
+    let name = shell
+        .to_possible_value()
+        .map(|value| value.get_name().to_owned())
+        .unwrap_or_default();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&name).ok_or_else(|| {
+        Error::InvalidArgument(format!("Completions for {} are not supported", name))
+    })?;
+
+    let command = Cli::command();
+    let bin = command.get_name();
+    let executable = env::current_exe()?;
+    completer.write_registration(COMPLETE_ENV, bin, bin, &executable.to_string_lossy(), buf)?;
+    Ok(())
+}
+
+/// Names of tokens in databases selected by completed command line. Names are read from key files
+/// without password, so nothing is completed for sealed databases
+pub fn token_names() -> Vec<CompletionCandidate> {
+    let Ok(config) = Config::load() else {
+        return Vec::new();
+    };
+    let Some(matches) = Cli::command()
+        .ignore_errors(true)
+        .try_get_matches_from(completed_args())
+        .ok()
+    else {
+        return Vec::new();
+    };
+
+    let profiles = matches
+        .get_many::<String>("profiles")
+        .map(|profiles| profiles.cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let databases = config.databases(
+        matches.get_one::<PathBuf>("keyfile").map(PathBuf::as_path),
+        &profiles,
+        matches.get_flag("all_profiles"),
+    );
+
+    database_token_names(databases.unwrap_or_default())
+}
+
+/// Token names of all profiles prefixed with profile, for `copy` and `move`
+pub fn profile_token_names() -> Vec<CompletionCandidate> {
+    let Ok(config) = Config::load() else {
+        return Vec::new();
+    };
+
+    database_token_names(
+        config
+            .profiles
+            .keys()
+            .filter_map(|profile| config.profile(profile).ok())
+            .collect(),
+    )
+}
+
+fn database_token_names(databases: Vec<Database>) -> Vec<CompletionCandidate> {
+    databases
+        .iter()
+        .filter_map(|database| Some((database, KeyFile::load(&database.path).ok()?)))
+        .flat_map(|(database, key_file)| {
+            key_file
+                .tokens
+                .keys()
+                .map(|name| CompletionCandidate::new(database.token_name(name)))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Words of command line being completed, completion script passes them after `--`
+fn completed_args() -> Vec<OsString> {
+    let args = env::args_os().collect::<Vec<_>>();
+    match args.iter().position(|arg| arg == "--") {
+        Some(index) => args[index + 1..].to_vec(),
+        None => Vec::new(),
+    }
+}
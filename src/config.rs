@@ -0,0 +1,417 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs};
+use termion::color::{AnsiValue, Fg, Reset};
+use termion::event::Key;
+
+use crate::{clock::DEFAULT_SERVER, error::Error, profiles::Database};
+
+const CONFIG_ENV: &str = "OTP_CLI_CONFIG";
+
+/// User settings from `config.toml`, command-line arguments take precedence over them
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Database used when neither key file nor profile is specified
+    pub database: Option<PathBuf>,
+    /// Named databases selected with `--profile`, several of them may be opened at once
+    pub profiles: BTreeMap<String, PathBuf>,
+    pub interface: Interface,
+    pub keys: KeyBindings,
+    pub clipboard: ClipboardSettings,
+    pub unlock: UnlockSettings,
+    pub theme: Theme,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Interface {
+    /// Tokens shown on one page of interactive mode
+    pub page_size: usize,
+    /// How often codes are redrawn (e.g. `1s`, `500ms`)
+    #[serde(deserialize_with = "duration")]
+    pub refresh_interval: Duration,
+    /// Next code is shown (and copied with `copy_next`) when current one expires in less than
+    /// this time
+    #[serde(deserialize_with = "duration")]
+    pub next_code_threshold: Duration,
+    /// Compare system clock with SNTP server when interactive mode starts, off by default since
+    /// it sends request to network server
+    pub clock_check: bool,
+    pub clock_server: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClipboardSettings {
+    /// Copy next code instead of current one when it is about to expire
+    pub copy_next: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnlockSettings {
+    /// Helper command for databases that require challenge-response
+    pub challenge_command: Option<String>,
+    /// Key file for databases that require it, `~` is expanded
+    pub key_file: Option<PathBuf>,
+}
+
+/// Keys of `use` and interactive mode, `Esc` and `Ctrl+c` always exit
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub copy: KeyBinding,
+    /// Exits `use` mode, returns to token list in interactive mode
+    pub back: KeyBinding,
+    pub next_group: KeyBinding,
+    /// Switches between databases when several profiles are opened
+    pub next_profile: KeyBinding,
+    /// Clears token name input, `Del` clears it as well
+    pub clear: KeyBinding,
+}
+
+/// Key written as `c`, `tab`, `esc`, `del`, `backspace` or `ctrl+d`, name is kept for hints
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyBinding {
+    pub key: Key,
+    pub name: String,
+}
+
+/// Colors are ANSI color names (`red`, `bright green`) or numbers from 0 to 255
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub code: Color,
+    pub warning: Color,
+    pub error: Color,
+}
+
+/// Terminal color, `none` leaves terminal default
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(Option<u8>);
+
+impl Config {
+    /// Reads config file, defaults are used if it does not exist
+    pub fn load() -> Result<Self> {
+        let Some(path) = config_path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let invalid = |message: &str| Error::Config(format!("{}: {}", path.display(), message));
+        let config: Self =
+            toml::from_str(&fs::read_to_string(&path)?).map_err(|err| invalid(&err.to_string()))?;
+
+        if config.interface.page_size == 0 {
+            Err(invalid("page_size must be greater than zero"))?;
+        }
+        if config.interface.refresh_interval.is_zero() {
+            Err(invalid("refresh_interval must be greater than zero"))?;
+        }
+        config
+            .keys
+            .check_clashes()
+            .map_err(|message| invalid(&message))?;
+
+        Ok(config)
+    }
+
+    /// Databases to open: key file from command line, then databases of given profiles (all
+    /// profiles if `all` is set), then default one
+    pub fn databases(
+        &self,
+        keyfile: Option<&Path>,
+        profiles: &[String],
+        all: bool,
+    ) -> Result<Vec<Database>> {
+        if let Some(keyfile) = keyfile {
+            return Ok(vec![Database::new(None, keyfile.to_owned())]);
+        }
+
+        let profiles = match all {
+            true => self.profiles.keys().cloned().collect(),
+            false => profiles.to_vec(),
+        };
+        if profiles.is_empty() {
+            return Ok(self
+                .database
+                .as_deref()
+                .map(|path| Database::new(None, expand_home(path)))
+                .into_iter()
+                .collect());
+        }
+
+        let mut databases = profiles
+            .into_iter()
+            .map(|profile| self.profile(&profile))
+            .collect::<Result<Vec<_>>>()?;
+        // Token names need profile prefix only to tell databases apart
+        if let [database] = databases.as_mut_slice() {
+            database.profile = None;
+        }
+        Ok(databases)
+    }
+
+    /// Key file from `[unlock]` section with home directory expanded
+    pub fn unlock_key_file(&self) -> Option<PathBuf> {
+        self.unlock.key_file.as_deref().map(expand_home)
+    }
+
+    /// Database of profile defined in config file
+    pub fn profile(&self, profile: &str) -> Result<Database> {
+        Ok(self
+            .profiles
+            .get(profile)
+            .map(|path| Database::new(Some(profile.to_owned()), expand_home(path)))
+            .ok_or_else(|| {
+                Error::Config(format!("Profile {} is not defined in config file", profile))
+            })?)
+    }
+}
+
+impl Default for Interface {
+    fn default() -> Self {
+        Self {
+            page_size: 5,
+            refresh_interval: Duration::from_secs(1),
+            next_code_threshold: Duration::from_secs(5),
+            clock_check: false,
+            clock_server: DEFAULT_SERVER.to_owned(),
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            copy: KeyBinding::new(Key::Char('c'), "c"),
+            back: KeyBinding::new(Key::Char('q'), "q"),
+            next_group: KeyBinding::new(Key::Char('\t'), "Tab"),
+            next_profile: KeyBinding::new(Key::Ctrl('p'), "Ctrl+p"),
+            clear: KeyBinding::new(Key::Ctrl('d'), "Ctrl+d"),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Fails if key is bound twice or takes key that has other meaning: `Esc` and `Ctrl+c` always
+    /// exit, digits copy code by index in `use` mode, characters, `Enter` and `Backspace` edit
+    /// token name in token list
+    fn check_clashes(&self) -> Result<(), String> {
+        let bindings = [
+            ("copy", &self.copy),
+            ("back", &self.back),
+            ("next_group", &self.next_group),
+            ("next_profile", &self.next_profile),
+            ("clear", &self.clear),
+        ];
+        let token_list_keys = ["next_group", "next_profile", "clear"];
+
+        for (index, (name, binding)) in bindings.iter().enumerate() {
+            if let Some((other, _)) = bindings[index + 1..]
+                .iter()
+                .find(|(_, other)| other.key == binding.key)
+            {
+                Err(format!("{} and {} are bound to the same key", name, other))?;
+            }
+
+            let reserved = match binding.key {
+                Key::Esc | Key::Ctrl('c') => Some("exits"),
+                Key::Char(ch) if ch.is_ascii_digit() && !token_list_keys.contains(name) => {
+                    Some("copies code by index in `use` mode")
+                }
+                Key::Char(ch) if ch != '\t' && token_list_keys.contains(name) => {
+                    Some("edits token name in token list")
+                }
+                Key::Backspace if token_list_keys.contains(name) => {
+                    Some("edits token name in token list")
+                }
+                _ => None,
+            };
+            if let Some(reserved) = reserved {
+                Err(format!(
+                    "{} can't be bound to {}, it {}",
+                    name, binding.name, reserved
+                ))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl KeyBinding {
+    fn new(key: Key, name: &str) -> Self {
+        Self {
+            key,
+            name: name.to_owned(),
+        }
+    }
+
+    pub fn matches(&self, key: &Key) -> bool {
+        self.key == *key
+    }
+}
+
+impl TryFrom<String> for KeyBinding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let lowercase = value.to_lowercase();
+        let mut chars = value.chars();
+
+        let binding = match (chars.next(), chars.next(), lowercase.as_str()) {
+            (Some(ch), None, _) => Self::new(Key::Char(ch), &value),
+            (_, _, "tab") => Self::new(Key::Char('\t'), "Tab"),
+            (_, _, "esc") => Self::new(Key::Esc, "Esc"),
+            (_, _, "del" | "delete") => Self::new(Key::Delete, "Del"),
+            (_, _, "backspace") => Self::new(Key::Backspace, "Backspace"),
+            (_, _, "enter") => Self::new(Key::Char('\n'), "Enter"),
+            _ => match lowercase
+                .strip_prefix("ctrl+")
+                .map(|rest| rest.chars().collect::<Vec<_>>())
+            {
+                Some(rest) if rest.len() == 1 => {
+                    Self::new(Key::Ctrl(rest[0]), &format!("Ctrl+{}", rest[0]))
+                }
+                _ => Err(format!("unknown key: {}", value))?,
+            },
+        };
+
+        Ok(binding)
+    }
+}
+
+impl Color {
+    /// Wraps text in color escape codes
+    pub fn paint(&self, text: &str) -> String {
+        match self.0 {
+            Some(value) => format!("{}{}{}", Fg(AnsiValue(value)), text, Fg(Reset)),
+            None => text.to_owned(),
+        }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        const NAMES: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+
+        let name = value.trim().to_lowercase();
+        if name == "none" || name == "default" {
+            return Ok(Self(None));
+        }
+        if let Ok(value) = name.parse::<u8>() {
+            return Ok(Self(Some(value)));
+        }
+
+        let (bright, base) = match name.strip_prefix("bright ") {
+            Some(base) => (true, base),
+            None => (false, name.as_str()),
+        };
+        NAMES
+            .iter()
+            .position(|color| *color == base)
+            .map(|index| Self(Some(index as u8 + if bright { 8 } else { 0 })))
+            .ok_or_else(|| format!("unknown color: {}", value))
+    }
+}
+
+/// Config file path: `$OTP_CLI_CONFIG`, or `otp-cli/config.toml` in `$XDG_CONFIG_HOME` (`~/.config`
+/// if it is not set)
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_dir.join("otp-cli").join("config.toml"))
+}
+
+/// Replaces leading `~` with home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bindings: &[(&str, &str)]) -> KeyBindings {
+        let mut keys = KeyBindings::default();
+        for (name, key) in bindings {
+            let binding = KeyBinding::try_from(key.to_string()).unwrap();
+            match *name {
+                "copy" => keys.copy = binding,
+                "back" => keys.back = binding,
+                "next_group" => keys.next_group = binding,
+                "next_profile" => keys.next_profile = binding,
+                "clear" => keys.clear = binding,
+                _ => unreachable!(),
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn key_clashes() {
+        assert_eq!(KeyBindings::default().check_clashes(), Ok(()));
+        assert_eq!(
+            keys(&[("copy", "y"), ("next_group", "ctrl+g")]).check_clashes(),
+            Ok(())
+        );
+
+        let errors = [
+            (
+                keys(&[("copy", "q")]),
+                "copy and back are bound to the same key",
+            ),
+            (
+                keys(&[("next_profile", "ctrl+d")]),
+                "next_profile and clear are bound to the same key",
+            ),
+            (
+                keys(&[("back", "esc")]),
+                "back can't be bound to Esc, it exits",
+            ),
+            (
+                keys(&[("copy", "1")]),
+                "copy can't be bound to 1, it copies code by index in `use` mode",
+            ),
+            (
+                keys(&[("next_group", "g")]),
+                "next_group can't be bound to g, it edits token name in token list",
+            ),
+            (
+                keys(&[("clear", "backspace")]),
+                "clear can't be bound to Backspace, it edits token name in token list",
+            ),
+        ];
+        for (keys, error) in errors {
+            assert_eq!(keys.check_clashes(), Err(error.to_owned()));
+        }
+    }
+}
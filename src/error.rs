@@ -0,0 +1,153 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors of database and backup operations, each kind has its own process exit code so scripts
+/// can tell them apart
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Master key derived from credentials does not match database (or database was modified),
+    /// or backup password is wrong
+    #[error("Wrong password or unlock factors, or file was modified")]
+    WrongPassword,
+    /// Database credentials are not provided or are incomplete
+    #[error("{0}")]
+    CredentialsRequired(String),
+    #[error("Database is corrupted: {0}")]
+    CorruptDatabase(String),
+    /// Database version, token type or backup contents this version does not handle
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+    /// Invalid backup file, token url or secret, `line` is 1-based line of input file
+    #[error("{}{message}", location(file, line))]
+    Parse {
+        file: Option<PathBuf>,
+        line: Option<usize>,
+        message: String,
+    },
+    #[error("Token {0} not found in file")]
+    TokenNotFound(String),
+    #[error("No tokens with tag {0} found in file")]
+    TagNotFound(String),
+    #[error("Token {0} already exists in file")]
+    TokenExists(String),
+    /// Helper command of challenge-response unlock factor failed
+    #[error("Challenge-response command failed: {0}")]
+    ChallengeResponse(String),
+    /// Verified code does not match any code of token within checked window
+    #[error("Code does not match token {0}")]
+    CodeMismatch(String),
+    /// Invalid command-line argument value or combination that clap can't check
+    #[error("{0}")]
+    InvalidArgument(String),
+    /// Config file is malformed, has invalid values or refers to something it does not define
+    #[error("{0}")]
+    Config(String),
+    /// Agent socket is unsafe, unreachable, or agent sent unexpected response
+    #[error("{0}")]
+    Agent(String),
+    /// SNTP server is unreachable or sent invalid response
+    #[error("{0}")]
+    Sntp(String),
+    /// System clock drift is large enough to break time based codes
+    #[error("{0}")]
+    ClockDrift(String),
+    #[error("Crypto error: {0}")]
+    Crypto(String),
+    #[error("Clipboard error: {0}")]
+    Clipboard(String),
+    #[error("Terminal error: {0}")]
+    Terminal(#[source] io::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Parse error without line information
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::Parse {
+            file: None,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    /// Sets line of parse error if it is not set yet
+    pub fn at_line(self, line: usize) -> Self {
+        match self {
+            Self::Parse {
+                file,
+                line: None,
+                message,
+            } => Self::Parse {
+                file,
+                line: Some(line),
+                message,
+            },
+            err => err,
+        }
+    }
+
+    /// Sets file of parse error if it is not set yet
+    pub fn in_file(self, path: &Path) -> Self {
+        match self {
+            Self::Parse {
+                file: None,
+                line,
+                message,
+            } => Self::Parse {
+                file: Some(path.to_owned()),
+                line,
+                message,
+            },
+            err => err,
+        }
+    }
+
+    /// Process exit code, 1 is left for errors of other kinds, 2 is shared with invalid arguments
+    /// reported by clap
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::InvalidArgument(_) => 2,
+            Self::WrongPassword => 3,
+            Self::CredentialsRequired(_) | Self::ChallengeResponse(_) => 4,
+            Self::CorruptDatabase(_) => 5,
+            Self::UnsupportedFormat(_) => 6,
+            Self::Parse { .. } => 7,
+            Self::TokenNotFound(_) | Self::TagNotFound(_) | Self::TokenExists(_) => 8,
+            Self::Crypto(_) => 9,
+            Self::Clipboard(_) => 10,
+            Self::Terminal(_) => 11,
+            Self::Io(_) => 12,
+            Self::CodeMismatch(_) => 13,
+            Self::Config(_) => 14,
+            Self::Agent(_) => 15,
+            Self::Sntp(_) => 16,
+            Self::ClockDrift(_) => 17,
+        }
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::CorruptDatabase(err.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::CorruptDatabase(err.to_string())
+    }
+}
+
+/// Prefix of parse error message pointing to where error is
+fn location(file: &Option<PathBuf>, line: &Option<usize>) -> String {
+    match (file, line) {
+        (Some(file), Some(line)) => format!("{}:{}: ", file.display(), line),
+        (Some(file), None) => format!("{}: ", file.display()),
+        (None, Some(line)) => format!("Line {}: ", line),
+        (None, None) => String::new(),
+    }
+}
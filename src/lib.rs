@@ -0,0 +1,43 @@
+//! Reading and writing otp-cli token databases.
+//!
+//! Database is a TOML file ([`KeyFile`]) with tokens ([`Token`]) which keys are encrypted with
+//! master key derived from password and optional additional unlock factors. [`Vault`] is unlocked
+//! database: it decrypts token keys and generates codes. Authenticator backups can be read with
+//! functions from [`parsers`].
+//!
+//! Printing current code of token:
+//!
+//! ```no_run
+//! use otp_cli::{Credentials, Vault};
+//!
+//! # fn main() -> otp_cli::Result<()> {
+//! let mut vault = Vault::open("otp-keys.toml".as_ref(), |_unlock_factors| {
+//!     Ok(Credentials {
+//!         password: String::from("password").into(),
+//!         key_file: None,
+//!         challenge_response: None,
+//!     })
+//! })?;
+//!
+//! println!("{}", vault.generate_code("github")?);
+//! // Counter of HOTP token was advanced
+//! vault.save()?;
+//! # Ok(())
+//! # }
+//! ```
+
+/// Error type of library operations
+pub mod error;
+/// One-time code generation
+pub mod otp;
+/// Readers of authenticator backups, tokens they return have unencrypted keys
+pub mod parsers;
+mod proto;
+/// Database and token types
+pub mod types;
+/// Database loading, unlocking and token key encryption
+pub mod vault;
+
+pub use error::{Error, Result};
+pub use types::{KeyFile, SecretBytes, SecretString, Token, TokenAlgorithm, TokenType};
+pub use vault::{Credentials, Vault};
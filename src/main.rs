@@ -1,13 +1,31 @@
-use anyhow::Result;
+use otp_cli::{error, otp, parsers, types, vault};
+use std::process;
 
+mod agent;
+mod buffered_stdout;
+mod challenge_response;
 mod cli;
+mod clipboard;
+mod clock;
+mod code_view;
 mod commands;
-mod parsers;
-mod proto;
-mod types;
+mod completions;
+mod config;
+mod man;
+mod profiles;
+mod prompt;
 
 use cli::Cli;
 
-fn main() -> Result<()> {
-    Cli::run()
+fn main() {
+    completions::complete();
+
+    if let Err(err) = Cli::run() {
+        eprintln!("Error: {:?}", err);
+        process::exit(
+            err.downcast_ref::<error::Error>()
+                .map(error::Error::exit_code)
+                .unwrap_or(1),
+        );
+    }
 }
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use clap::{Command, CommandFactory, ValueEnum};
+use clap_mangen::{
+    Man,
+    roff::{Roff, bold, roman},
+};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cli::{BackupType, Cli},
+    error::Error,
+};
+
+/// Database path used in examples
+const EXAMPLE_DATABASE: &str = "./otp-keys.toml";
+
+/// Writes man page of subcommand with given name, or of root command if name is not specified
+pub fn write_page(name: Option<&str>, w: &mut dyn Write) -> Result<()> {
+    let command = root_command();
+    let command = match name {
+        Some(name) => command
+            .find_subcommand(name)
+            .ok_or_else(|| Error::InvalidArgument(format!("Unknown command {}", name)))?,
+        None => &command,
+    };
+
+    render(command, w)
+}
+
+/// Writes man pages of root command and all subcommands to directory, returns paths of written
+/// files
+pub fn write_pages(out_dir: &Path) -> Result<Vec<PathBuf>> {
+    let command = root_command();
+
+    std::iter::once(&command)
+        .chain(command.get_subcommands())
+        .map(|command| {
+            let path = out_dir.join(Man::new(command.clone()).get_filename());
+            render(command, &mut File::create(&path)?)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Root command built from `Cli`, so subcommands are named `otp-cli-<command>` in their pages
+fn root_command() -> Command {
+    let mut command = Cli::command().disable_help_subcommand(true);
+    command.build();
+    command
+}
+
+/// Same sections `clap_mangen` renders, with examples put before version and authors
+fn render(command: &Command, w: &mut dyn Write) -> Result<()> {
+    // Subcommands have no version of their own
+    let man = Man::new(command.clone()).source(format!("otp-cli {}", env!("CARGO_PKG_VERSION")));
+
+    man.render_title(w)?;
+    man.render_name_section(w)?;
+    man.render_synopsis_section(w)?;
+    man.render_description_section(w)?;
+    man.render_options_section(w)?;
+    if command.has_subcommands() {
+        man.render_subcommands_section(w)?;
+    }
+
+    let examples = examples(command.get_name());
+    if !examples.is_empty() {
+        let mut roff = Roff::default();
+        roff.control("SH", ["EXAMPLES"]);
+        for (description, command_line) in examples {
+            roff.control("TP", []);
+            roff.text([bold(command_line)]);
+            roff.text([roman(description)]);
+        }
+        roff.to_writer(w)?;
+    }
+
+    if command.get_version().is_some() {
+        man.render_version_section(w)?;
+    }
+    if command.get_author().is_some() {
+        man.render_authors_section(w)?;
+    }
+
+    Ok(())
+}
+
+/// Examples of command usage with their descriptions, backup examples are generated for every
+/// backup type
+fn examples(command_name: &str) -> Vec<(String, String)> {
+    let backups = BackupType::value_variants().iter().map(|backup_type| {
+        let (file, description) = backup_example(*backup_type);
+        let name = backup_type
+            .to_possible_value()
+            .map(|value| value.get_name().to_owned())
+            .unwrap_or_default();
+        (name, file, description)
+    });
+
+    match command_name {
+        "parse" => backups
+            .map(|(name, file, description)| {
+                (
+                    format!("Print tokens from {}", description),
+                    format!("otp-cli parse -t {} -f {}", name, file),
+                )
+            })
+            .collect(),
+        "convert" | "otp-cli" => {
+            let mut examples = backups
+                .map(|(name, file, description)| {
+                    (
+                        format!("Import tokens from {}", description),
+                        format!(
+                            "otp-cli {} convert -t {} -i {}",
+                            EXAMPLE_DATABASE, name, file
+                        ),
+                    )
+                })
+                .collect::<Vec<_>>();
+            if command_name == "otp-cli" {
+                examples.extend(usage_examples());
+            }
+            examples
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Backup file name and description of its contents
+fn backup_example(backup_type: BackupType) -> (&'static str, &'static str) {
+    match backup_type {
+        BackupType::TokensList => (
+            "new_tokens.txt",
+            "tokens list (otpauth:// urls, one per line)",
+        ),
+        BackupType::FreeOtp => ("externalBackup", "FreeOTP backup"),
+        BackupType::GoogleAuth => (
+            "google_auth_tokens.txt",
+            "Google Authenticator export (otpauth-migration:// urls from QR codes, one per line)",
+        ),
+    }
+}
+
+fn usage_examples() -> Vec<(String, String)> {
+    [
+        (
+            "Add token, secret is asked without echoing it",
+            "add -n github --issuer GitHub --label alice",
+        ),
+        (
+            "Show code of token and copy it to clipboard",
+            "use -t github",
+        ),
+        ("Print code of token and exit", "code -t github"),
+        ("Pick tokens from list in interactive mode", "start"),
+    ]
+    .into_iter()
+    .map(|(description, arguments)| {
+        (
+            description.to_owned(),
+            format!("otp-cli {} {}", EXAMPLE_DATABASE, arguments),
+        )
+    })
+    .collect()
+}
@@ -0,0 +1,283 @@
+use hmac::{Hmac, Mac, digest::KeyInit};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+use crate::types::{SecretBytes, Token, TokenAlgorithm, TokenType};
+
+/// Shortest code of HOTP and TOTP tokens, RFC 4226 requires at least 6 digits but some services
+/// use 4 digit codes
+pub const MIN_DIGITS: u32 = 4;
+/// Longest code of HOTP and TOTP tokens, truncated value has 31 bits so longer codes make no sense
+pub const MAX_DIGITS: u32 = 10;
+
+/// Yandex Key secrets are 16 bytes, longer ones carry checksum after them
+const YANDEX_SECRET_LENGTH: usize = 16;
+
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+
+/// Generates codes of unlocked token
+#[derive(Clone)]
+pub struct Generator {
+    token_type: TokenType,
+    algorithm: TokenAlgorithm,
+    digits: u32,
+    period: u64,
+    t0: u64,
+    counter: u64,
+    secret: SecretBytes,
+    pin: Option<SecretBytes>,
+}
+
+/// How long generated code can be used
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Validity {
+    /// Seconds left until code of time based token changes
+    Ttl(u64),
+    /// Counter value code of HOTP token was generated for
+    Counter(u64),
+}
+
+/// Code that matched verified one
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// Steps from time step of verification time (from stored counter for HOTP tokens), negative
+    /// for past codes
+    pub offset: i64,
+    /// Counter code was generated for, time step for time based tokens
+    pub counter: u64,
+}
+
+impl Generator {
+    /// Creates generator from token parameters and its decrypted secret and PIN
+    pub fn new(token: &Token, secret: SecretBytes, pin: Option<SecretBytes>) -> Result<Self> {
+        if token.token_type.requires_pin() && pin.is_none() {
+            Err(Error::CredentialsRequired(format!(
+                "PIN is required for {:?} token",
+                token.token_type
+            )))?;
+        }
+        if secret.is_empty() {
+            Err(Error::CorruptDatabase("token secret is empty".to_owned()))?;
+        }
+        if token.token_type != TokenType::Hotp && token.period == 0 {
+            Err(Error::CorruptDatabase(
+                "token period must be greater than zero".to_owned(),
+            ))?;
+        }
+        if matches!(token.token_type, TokenType::Hotp | TokenType::Totp)
+            && !(MIN_DIGITS..=MAX_DIGITS).contains(&token.digits)
+        {
+            Err(Error::UnsupportedFormat(format!(
+                "token has {} digits, codes of {} to {} digits are supported",
+                token.digits, MIN_DIGITS, MAX_DIGITS
+            )))?;
+        }
+        if let Some((digits, period)) = token.token_type.fixed_shape()
+            && (token.digits, token.period) != (digits, period)
+        {
+            Err(Error::UnsupportedFormat(format!(
+                "{:?} token has {} digits and {}s period, only {} digits and {}s period are supported",
+                token.token_type, token.digits, token.period, digits, period
+            )))?;
+        }
+
+        Ok(Self {
+            token_type: token.token_type,
+            algorithm: token.algorithm,
+            digits: token.digits,
+            period: token.period,
+            t0: token.t0,
+            counter: token.counter.into(),
+            secret,
+            pin,
+        })
+    }
+
+    /// Code valid at given Unix time, HOTP tokens ignore time and use their counter
+    pub fn generate_at(&self, time: u64) -> Result<String> {
+        self.generate_for_counter(self.counter_at(time))
+    }
+
+    /// Code `offset` steps before (negative) or after code valid at given time, for HOTP tokens
+    /// steps are counted from stored counter
+    pub fn generate_with_offset(&self, time: u64, offset: i64) -> Result<String> {
+        let counter = self
+            .counter_at(time)
+            .checked_add_signed(offset)
+            .ok_or_else(|| Error::InvalidArgument(format!("Offset {} is out of range", offset)))?;
+        self.generate_for_counter(counter)
+    }
+
+    pub fn generate_current(&self) -> Result<String> {
+        self.generate_at(now()?)
+    }
+
+    /// Code for given counter value, time based tokens use number of periods since T0 as counter
+    pub fn generate_for_counter(&self, counter: u64) -> Result<String> {
+        match self.token_type {
+            TokenType::Motp => Ok(self.motp(counter)),
+            TokenType::Yandex => self.yandex(counter),
+            TokenType::Steam => Ok(steam_code(truncate(&self.hmac(counter)?), self.digits)),
+            TokenType::Hotp | TokenType::Totp => {
+                Ok(decimal_code(truncate(&self.hmac(counter)?), self.digits))
+            }
+        }
+    }
+
+    /// Checks code against codes within `window` steps around given time (around stored counter for
+    /// HOTP tokens), nearest steps are checked first, whitespace and letter case are ignored
+    pub fn verify_at(&self, code: &str, time: u64, window: u32) -> Result<Option<Match>> {
+        let code = code.split_whitespace().collect::<String>();
+        let base = self.counter_at(time);
+
+        for distance in 0..=i64::from(window) {
+            for offset in [-distance, distance] {
+                let Some(counter) = base.checked_add_signed(offset) else {
+                    continue;
+                };
+                if self
+                    .generate_for_counter(counter)?
+                    .eq_ignore_ascii_case(&code)
+                {
+                    return Ok(Some(Match { offset, counter }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn verify_current(&self, code: &str, window: u32) -> Result<Option<Match>> {
+        self.verify_at(code, now()?, window)
+    }
+
+    /// Seconds left until current code changes, or counter for HOTP tokens
+    pub fn validity(&self) -> Result<Validity> {
+        Ok(self.validity_at(now()?))
+    }
+
+    pub fn validity_at(&self, time: u64) -> Validity {
+        match self.token_type {
+            TokenType::Hotp => Validity::Counter(self.counter),
+            _ => Validity::Ttl(self.period - time.saturating_sub(self.t0) % self.period),
+        }
+    }
+
+    /// Counter of code valid at given time: number of periods passed since T0 (RFC 6238, section
+    /// 4.2), or stored counter of HOTP token
+    fn counter_at(&self, time: u64) -> u64 {
+        match self.token_type {
+            TokenType::Hotp => self.counter,
+            _ => time.saturating_sub(self.t0) / self.period,
+        }
+    }
+
+    /// HMAC of counter (RFC 4226, section 5.2)
+    fn hmac(&self, counter: u64) -> Result<Vec<u8>> {
+        match self.algorithm {
+            TokenAlgorithm::Sha1 => hmac::<Hmac<Sha1>>(&self.secret, counter),
+            TokenAlgorithm::Sha256 => hmac::<Hmac<Sha256>>(&self.secret, counter),
+            TokenAlgorithm::Sha512 => hmac::<Hmac<Sha512>>(&self.secret, counter),
+            TokenAlgorithm::Md5 => hmac::<Hmac<Md5>>(&self.secret, counter),
+        }
+    }
+
+    /// Mobile-OTP: first hex digits of MD5 over time step, hex encoded secret and PIN. Secret is
+    /// hashed in lowercase hex like mOTP clients generate it, so secret entered in uppercase gives
+    /// codes of its lowercase form
+    fn motp(&self, time_step: u64) -> String {
+        let input = Zeroizing::new(
+            [
+                time_step.to_string().as_bytes(),
+                hex::encode(&self.secret[..]).as_bytes(),
+                self.pin(),
+            ]
+            .concat(),
+        );
+
+        let mut code = hex::encode(Md5::digest(&input[..]));
+        code.truncate(self.digits as usize);
+        code
+    }
+
+    /// Yandex Key: HMAC-SHA256 keyed with hash of PIN and secret, code is written with latin letters
+    fn yandex(&self, time_step: u64) -> Result<String> {
+        let secret = &self.secret[..self.secret.len().min(YANDEX_SECRET_LENGTH)];
+        let key_input = Zeroizing::new([self.pin(), secret].concat());
+        let key_hash = Zeroizing::new(Sha256::digest(&key_input[..]).to_vec());
+        // Leading zero byte is dropped, as Yandex implementation stores hash as big number
+        let key = match key_hash.first() {
+            Some(0) => &key_hash[1..],
+            _ => &key_hash[..],
+        };
+
+        let hash = hmac::<Hmac<Sha256>>(key, time_step)?;
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let mut value = u64::from_be_bytes(hash[offset..offset + 8].try_into().unwrap());
+        value &= 0x7fff_ffff_ffff_ffff;
+        value %= 26u64.pow(self.digits);
+
+        let mut code = vec![b'a'; self.digits as usize];
+        for letter in code.iter_mut().rev() {
+            *letter += (value % 26) as u8;
+            value /= 26;
+        }
+        Ok(String::from_utf8(code).unwrap())
+    }
+
+    fn pin(&self) -> &[u8] {
+        self.pin.as_deref().unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Validity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Validity::Ttl(seconds) => write!(f, "ttl: {}", seconds),
+            Validity::Counter(counter) => write!(f, "counter: {}", counter),
+        }
+    }
+}
+
+fn hmac<M: Mac + KeyInit>(key: &[u8], counter: u64) -> Result<Vec<u8>> {
+    let mut mac = <M as Mac>::new_from_slice(key).map_err(|err| Error::Crypto(err.to_string()))?;
+    mac.update(&counter.to_be_bytes());
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Dynamic truncation (RFC 4226, section 5.3)
+fn truncate(hash: &[u8]) -> u32 {
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff
+}
+
+fn decimal_code(value: u32, digits: u32) -> String {
+    let code = value as u64 % 10u64.pow(digits);
+    format!("{:0width$}", code, width = digits as usize)
+}
+
+/// Steam Guard code: truncated value written in Steam alphabet, lowest character first
+fn steam_code(mut value: u32, digits: u32) -> String {
+    let base = STEAM_ALPHABET.len() as u32;
+    (0..digits)
+        .map(|_| {
+            let ch = STEAM_ALPHABET[(value % base) as usize] as char;
+            value /= base;
+            ch
+        })
+        .collect()
+}
+
+/// Current Unix time in seconds
+pub fn now() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(std::io::Error::other)?
+        .as_secs())
+}
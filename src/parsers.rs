@@ -1,7 +1,146 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::types::Token;
+
 mod parse_freeotp_backup;
 mod parse_googleauth_backup;
+mod parse_otpauth_url;
 mod parse_tokens_list;
 
 pub use parse_freeotp_backup::parse_freeotp_backup;
 pub use parse_googleauth_backup::parse_googleauth_backup;
+pub use parse_otpauth_url::parse_otpauth_url;
 pub use parse_tokens_list::parse_tokens_list;
+
+/// Tokens read from backup file. Supported formats have no groups, tags or favorites, so these
+/// fields of parsed tokens are left empty
+#[derive(Debug, Default)]
+pub struct ParsedBackup {
+    pub tokens: Vec<Token>,
+    /// Errors of entries that were skipped, with file and line they are on
+    pub rejected: Vec<Error>,
+}
+
+/// Steam tokens are recognized by issuer in apps that have no separate type for them
+fn is_steam_issuer(issuer: &str) -> bool {
+    issuer.trim().eq_ignore_ascii_case("steam")
+}
+
+/// Parses line based backup file, blank lines and lines starting with `#` are ignored. Invalid
+/// lines fail whole file unless `skip_invalid` is set, then they are collected in
+/// [`ParsedBackup::rejected`]
+fn parse_lines(
+    backup_file: &Path,
+    skip_invalid: bool,
+    parse_line: impl Fn(&str) -> Result<Vec<Token>>,
+) -> Result<ParsedBackup> {
+    let mut parsed = ParsedBackup::default();
+
+    for (index, line) in fs::read_to_string(backup_file)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_line(line) {
+            Ok(mut tokens) => parsed.tokens.append(&mut tokens),
+            Err(err) => {
+                let err = err.at_line(index + 1).in_file(backup_file);
+                match skip_invalid {
+                    true => parsed.rejected.push(err),
+                    false => Err(err)?,
+                }
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, TempPath};
+
+    /// Writes backup fixture to temp file, which is removed when returned path is dropped
+    fn fixture(contents: &str) -> TempPath {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.into_temp_path()
+    }
+
+    /// Line parser producing token labelled with line contents, lines starting with `bad` fail
+    fn parse_line(line: &str) -> Result<Vec<Token>> {
+        match line.starts_with("bad") {
+            true => Err(Error::parse(format!("Invalid line: {}", line))),
+            false => Ok(vec![Token {
+                label: line.to_owned(),
+                ..Default::default()
+            }]),
+        }
+    }
+
+    fn labels(parsed: &ParsedBackup) -> Vec<&str> {
+        parsed
+            .tokens
+            .iter()
+            .map(|token| token.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let path = fixture("# comment\n\none\n   \n  # indented comment\n  two  \n");
+        let parsed = parse_lines(&path, false, parse_line).unwrap();
+
+        assert_eq!(labels(&parsed), ["one", "two"]);
+        assert!(parsed.rejected.is_empty());
+    }
+
+    #[test]
+    fn invalid_line_fails_file() {
+        let path = fixture("one\n# comment\n\nbad two\nthree\n");
+        let err = parse_lines(&path, false, parse_line).unwrap_err();
+
+        assert!(matches!(err, Error::Parse { line: Some(4), .. }));
+        assert_eq!(
+            err.to_string(),
+            format!("{}:4: Invalid line: bad two", path.display())
+        );
+    }
+
+    #[test]
+    fn invalid_lines_are_collected() {
+        let path = fixture("bad one\ntwo\n\nbad three\nfour\n");
+        let parsed = parse_lines(&path, true, parse_line).unwrap();
+
+        assert_eq!(labels(&parsed), ["two", "four"]);
+        assert_eq!(
+            parsed
+                .rejected
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>(),
+            [
+                format!("{}:1: Invalid line: bad one", path.display()),
+                format!("{}:4: Invalid line: bad three", path.display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_list() {
+        let path =
+            fixture("# GitHub\notpauth://totp/GitHub:alice?secret=MZXW6\n\nhttps://example.com\n");
+        let parsed = parse_tokens_list(&path, true).unwrap();
+
+        assert_eq!(labels(&parsed), ["alice"]);
+        assert_eq!(
+            parsed.rejected[0].to_string(),
+            format!("{}:4: Token url must start with otpauth://", path.display())
+        );
+    }
+}
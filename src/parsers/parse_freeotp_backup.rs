@@ -1,23 +1,27 @@
 use aes_gcm::{AeadInPlace, Aes256Gcm, Nonce, aead::KeyInit};
-use anyhow::{Result, anyhow};
 use jaded::Parser;
 use pbkdf2::pbkdf2_hmac_array;
 use serde::Deserialize;
 use sha2::Sha512;
 use std::collections::HashMap;
-use std::{fs, path::PathBuf};
+use std::{fs, path::Path};
+use zeroize::Zeroizing;
 
-use crate::types::{Token, TokenAlgorithm, TokenType};
+use super::is_steam_issuer;
+use crate::error::{Error, Result};
+use crate::types::{SecretBytes, Token, TokenAlgorithm, TokenType};
 
-pub fn parse_freeotp_backup(backup_file: &PathBuf, password: &str) -> Result<Vec<Token>> {
+/// Parse FreeOTP backup (`externalBackup` file) encrypted with backup password
+pub fn parse_freeotp_backup(backup_file: &Path, password: &str) -> Result<Vec<Token>> {
     let mut entries = extract_file_contents(backup_file)?;
 
     let encrypted_master_key = entries
         .remove("masterKey")
-        .ok_or(anyhow!("No masterKey found in backup file"))
+        .ok_or_else(|| Error::parse("No masterKey found in backup file"))
         .and_then(|key_data| {
-            serde_json::from_str::<EncryptedMasterKey>(key_data.as_str())
-                .map_err(|err| anyhow!("Failed to deserialize master key data: {}", err))
+            serde_json::from_str::<EncryptedMasterKey>(key_data.as_str()).map_err(|err| {
+                Error::parse(format!("Failed to deserialize master key data: {}", err))
+            })
         })?;
 
     let master_key = decrypt_master_key(&encrypted_master_key, password)?;
@@ -31,27 +35,39 @@ pub fn parse_freeotp_backup(backup_file: &PathBuf, password: &str) -> Result<Vec
 
         let token_info_value = entries
             .get(format!("{}-token", key).as_str())
-            .ok_or(anyhow!("Failed to find key for token: {}", key))?;
+            .ok_or_else(|| Error::parse(format!("Failed to find key for token: {}", key)))?;
 
         let token_info = serde_json::from_str::<TokenInfo>(token_info_value.as_str())
-            .map_err(|err| anyhow!("Failed to deserialize token info: {}", err))?;
+            .map_err(|err| Error::parse(format!("Failed to deserialize token info: {}", err)))?;
 
         let encrypted_token_key_wrapper =
-            serde_json::from_str::<EncryptedTokenKeyWrapper>(value.as_str())
-                .map_err(|err| anyhow!("Failed to deserialize token key wrapper data: {}", err))?;
+            serde_json::from_str::<EncryptedTokenKeyWrapper>(value.as_str()).map_err(|err| {
+                Error::parse(format!(
+                    "Failed to deserialize token key wrapper data: {}",
+                    err
+                ))
+            })?;
 
         let encrypted_token_key =
             serde_json::from_str::<EncryptedKey>(encrypted_token_key_wrapper.key.as_str())
-                .map_err(|err| anyhow!("Failed to deserialize token key data: {}", err))?;
+                .map_err(|err| {
+                    Error::parse(format!("Failed to deserialize token key data: {}", err))
+                })?;
 
-        let token_key = decrypt_token_key(&encrypted_token_key, master_key.as_slice())?;
+        let token_key = decrypt_token_key(&encrypted_token_key, &master_key)?;
 
         let algorithm = match token_info.algo {
             Some(algo) => match algo.as_str() {
                 "SHA1" => TokenAlgorithm::Sha1,
                 "SHA256" => TokenAlgorithm::Sha256,
                 "SHA512" => TokenAlgorithm::Sha512,
-                _ => return Err(anyhow!("Unsupported token algorithm: {}", algo)),
+                "MD5" => TokenAlgorithm::Md5,
+                _ => {
+                    return Err(Error::UnsupportedFormat(format!(
+                        "token algorithm {}",
+                        algo
+                    )));
+                }
             },
             None => TokenAlgorithm::Sha1,
         };
@@ -59,10 +75,16 @@ pub fn parse_freeotp_backup(backup_file: &PathBuf, password: &str) -> Result<Vec
         let token_type = match token_info.token_type.as_str() {
             "HOTP" => TokenType::Hotp,
             "TOTP" => TokenType::Totp,
-            _ => return Err(anyhow!("Unsupported token type: {}", token_info.token_type)),
+            "STEAM" => TokenType::Steam,
+            _ => {
+                return Err(Error::UnsupportedFormat(format!(
+                    "token type {}",
+                    token_info.token_type
+                )));
+            }
         };
 
-        tokens.push(Token {
+        let mut token = Token {
             algorithm,
             counter: token_info.counter,
             digits: token_info.digits,
@@ -71,55 +93,70 @@ pub fn parse_freeotp_backup(backup_file: &PathBuf, password: &str) -> Result<Vec
             period: token_info.period,
             token_type,
             key: token_key,
-        });
+            ..Default::default()
+        };
+
+        // Steam tokens are usually stored as TOTP with Steam issuer
+        if token.token_type == TokenType::Steam
+            || (token.token_type == TokenType::Totp && is_steam_issuer(&token.issuer))
+        {
+            token.set_type(TokenType::Steam);
+        }
+
+        tokens.push(token);
     }
 
     Ok(tokens)
 }
 
-fn extract_file_contents(backup_file: &PathBuf) -> Result<HashMap<String, String>> {
+fn extract_file_contents(backup_file: &Path) -> Result<HashMap<String, String>> {
     let file = fs::File::open(backup_file)?;
-    let mut parser = Parser::new(file)?;
+    let mut parser = Parser::new(file).map_err(|err| Error::parse(err.to_string()))?;
 
-    let obj = parser.read()?;
+    let obj = parser.read().map_err(|err| Error::parse(err.to_string()))?;
 
     let java_hashmap = match obj {
         jaded::Content::Object(jaded::Value::Object(java_object)) => {
             if java_object.class_name() == "java.util.HashMap" {
                 Ok(java_object)
             } else {
-                Err(anyhow!(
+                Err(Error::parse(format!(
                     "Provided data is not Java HashMap:\n{:?}",
                     java_object
-                ))
+                )))
             }
         }
-        _ => Err(anyhow!("Provided data is not Java Object:\n{:?}", obj)),
+        _ => Err(Error::parse(format!(
+            "Provided data is not Java Object:\n{:?}",
+            obj
+        ))),
     }?;
 
     java_hashmap
         .get_annotation(0)
-        .ok_or(anyhow!(
-            "Failed to get annotation from Object:\n{:?}",
-            java_hashmap
-        ))
+        .ok_or_else(|| {
+            Error::parse(format!(
+                "Failed to get annotation from Object:\n{:?}",
+                java_hashmap
+            ))
+        })
         .and_then(|mut fields| {
             let _capacity = fields
                 .read_i32()
-                .map_err(|err| anyhow!("Failed to parse capacity: {:?}", err))?;
+                .map_err(|err| Error::parse(format!("Failed to parse capacity: {:?}", err)))?;
             let size = fields
                 .read_i32()
-                .map_err(|err| anyhow!("Failed to parse size: {:?}", err))?;
+                .map_err(|err| Error::parse(format!("Failed to parse size: {:?}", err)))?;
 
             let mut entries = HashMap::new();
 
             for _ in 0..size {
                 let k = fields
                     .read_object_as::<String>()
-                    .map_err(|err| anyhow!("Failed to parse key: {:?}", err))?;
+                    .map_err(|err| Error::parse(format!("Failed to parse key: {:?}", err)))?;
                 let v = fields
                     .read_object_as::<String>()
-                    .map_err(|err| anyhow!("Failed to parse value: {:?}", err))?;
+                    .map_err(|err| Error::parse(format!("Failed to parse value: {:?}", err)))?;
                 entries.insert(k, v);
             }
 
@@ -130,22 +167,22 @@ fn extract_file_contents(backup_file: &PathBuf) -> Result<HashMap<String, String
 fn decrypt_master_key(
     encrypted_master_key: &EncryptedMasterKey,
     password: &str,
-) -> Result<Vec<u8>> {
-    let master_pwd = pbkdf2_hmac_array::<Sha512, 32>(
+) -> Result<SecretBytes> {
+    let master_pwd = Zeroizing::new(pbkdf2_hmac_array::<Sha512, 32>(
         password.as_bytes(),
         from_java_bytes(encrypted_master_key.m_salt.as_slice()).as_slice(),
         encrypted_master_key.m_iterations,
-    );
+    ));
 
     let cipher = Aes256Gcm::new_from_slice(master_pwd.as_slice())
-        .map_err(|err| anyhow!("Failed to construct decryption key: {}", err))?;
+        .map_err(|err| Error::Crypto(err.to_string()))?;
 
-    let mut master_key: Vec<u8> = from_java_bytes(
+    let mut master_key = Zeroizing::new(from_java_bytes(
         encrypted_master_key
             .m_encrypted_key
             .m_cipher_text
             .as_slice(),
-    );
+    ));
 
     cipher
         .decrypt_in_place(
@@ -154,28 +191,30 @@ fn decrypt_master_key(
                     [4..16],
             ),
             encrypted_master_key.m_encrypted_key.m_token.as_bytes(),
-            &mut master_key,
+            &mut *master_key,
         )
-        .map_err(|err| anyhow!("Failed to decrypt master key: {}", err))?;
+        .map_err(|_| Error::WrongPassword)?;
 
-    Ok(master_key)
+    Ok(SecretBytes::from(master_key.to_vec()))
 }
 
-fn decrypt_token_key(encrypted_token_key: &EncryptedKey, master_key: &[u8]) -> Result<Vec<u8>> {
-    let cipher = Aes256Gcm::new_from_slice(master_key)
-        .map_err(|err| anyhow!("Failed to construct decryption key: {}", err))?;
+fn decrypt_token_key(encrypted_token_key: &EncryptedKey, master_key: &[u8]) -> Result<SecretBytes> {
+    let cipher =
+        Aes256Gcm::new_from_slice(master_key).map_err(|err| Error::Crypto(err.to_string()))?;
 
-    let mut token_key: Vec<u8> = from_java_bytes(encrypted_token_key.m_cipher_text.as_slice());
+    let mut token_key = Zeroizing::new(from_java_bytes(
+        encrypted_token_key.m_cipher_text.as_slice(),
+    ));
 
     cipher
         .decrypt_in_place(
             Nonce::from_slice(&from_java_bytes(encrypted_token_key.m_parameters.as_slice())[4..16]),
             encrypted_token_key.m_token.as_bytes(),
-            &mut token_key,
+            &mut *token_key,
         )
-        .map_err(|err| anyhow!("Failed to decrypt token key: {}", err))?;
+        .map_err(|err| Error::Crypto(format!("failed to decrypt token key: {}", err)))?;
 
-    Ok(token_key)
+    Ok(SecretBytes::from(token_key.to_vec()))
 }
 
 #[allow(dead_code)]
@@ -1,90 +1,160 @@
-use anyhow::{Result, anyhow};
 use base64::{self, Engine};
 use protobuf::Message;
-use std::{fs, path::PathBuf};
-use urlencoding;
+use std::path::Path;
+use zeroize::Zeroizing;
 
+use super::{ParsedBackup, is_steam_issuer, parse_lines};
 use crate::{
+    error::{Error, Result},
     proto::google_auth,
     types::{Token, TokenAlgorithm, TokenType},
 };
 
-pub fn parse_googleauth_backup(backup_file: &PathBuf) -> Result<Vec<Token>> {
-    fs::read_to_string(backup_file)?
-        .trim()
-        .lines()
-        .try_fold(Vec::new(), |mut tokens_list, row| {
-            let data = row.replace("otpauth-migration://offline?data=", "");
-
-            let token_string = urlencoding::decode(data.as_str()).map_err(|err| {
-                anyhow!("Encountered string that is not valie backup uri: {}", err)
-            })?;
-
-            let token_bytes = base64::engine::general_purpose::STANDARD
-                .decode(token_string.as_bytes())
-                .map_err(|err| anyhow!("Failed to decode token bytes from base64: {}", err))?;
-
-            let token_message =
-                google_auth::GoogleAuthBackup::parse_from_bytes(token_bytes.as_slice())
-                    .map_err(|err| anyhow!("Failed to parse token data: {}", err))?;
-
-            token_message
-                .otp_parameters
-                .into_iter()
-                .map(|token| {
-                    let algorithm = match token.algorithm.enum_value() {
-                        Ok(value) => match value {
-                            google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA1 => {
-                                Ok(TokenAlgorithm::Sha1)
-                            }
-                            google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA256 => {
-                                Ok(TokenAlgorithm::Sha256)
-                            }
-                            google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA512 => {
-                                Ok(TokenAlgorithm::Sha512)
-                            }
-                            _ => Err(anyhow!("Invalid token algorithm: {:?}", token.algorithm)),
-                        },
-                        Err(_) => Err(anyhow!("Invalid digits value: {:?}", token.digits)),
-                    }?;
-
-                    let token_type = match token.type_.enum_value() {
-                        Ok(value) => match value {
-                            google_auth::google_auth_backup::OtpType::OTP_TYPE_HOTP => {
-                                Ok(TokenType::Hotp)
-                            }
-                            google_auth::google_auth_backup::OtpType::OTP_TYPE_TOTP => {
-                                Ok(TokenType::Totp)
-                            }
-                            _ => Err(anyhow!("Invalid token type: {:?}", token.type_)),
-                        },
-                        Err(_) => Err(anyhow!("Invalid digits value: {:?}", token.digits)),
-                    }?;
-
-                    let digits = match token.digits.enum_value() {
-                        Ok(value) => match value {
-                            google_auth::google_auth_backup::Digits::DIGITS_SIX => Ok(6),
-                            google_auth::google_auth_backup::Digits::DIGITS_EIGHT => Ok(8),
-                            _ => Err(anyhow!("Invalid token type: {:?}", token.algorithm)),
-                        },
-                        Err(_) => Err(anyhow!("Invalid digits value: {:?}", token.digits)),
-                    }?;
-
-                    Ok(Token {
-                        algorithm,
-                        counter: token.counter as u32,
-                        digits: digits,
-                        issuer: token.issuer,
-                        label: token.name,
-                        period: 30,
-                        token_type,
-                        key: token.secret,
-                    })
-                })
-                .collect::<Result<Vec<_>>>()
-                .map(|mut tokens| {
-                    tokens_list.append(&mut tokens);
-                    tokens_list
-                })
+/// Parse file with Google Authenticator export urls (`otpauth-migration://...`), one url on each
+/// line
+pub fn parse_googleauth_backup(backup_file: &Path, skip_invalid: bool) -> Result<ParsedBackup> {
+    parse_lines(backup_file, skip_invalid, parse_export_url)
+}
+
+/// Parse single export url, it may contain several tokens
+fn parse_export_url(row: &str) -> Result<Vec<Token>> {
+    let data = row.replace("otpauth-migration://offline?data=", "");
+
+    let token_string = urlencoding::decode(data.as_str()).map_err(|err| {
+        Error::parse(format!(
+            "Encountered string that is not valid backup uri: {}",
+            err
+        ))
+    })?;
+
+    let token_bytes = base64::engine::general_purpose::STANDARD
+        .decode(token_string.as_bytes())
+        .map(Zeroizing::new)
+        .map_err(|err| {
+            Error::parse(format!("Failed to decode token bytes from base64: {}", err))
+        })?;
+
+    let token_message = google_auth::GoogleAuthBackup::parse_from_bytes(token_bytes.as_slice())
+        .map_err(|err| Error::parse(format!("Failed to parse token data: {}", err)))?;
+
+    token_message
+        .otp_parameters
+        .into_iter()
+        .map(|token| {
+            let algorithm = match token.algorithm.enum_value() {
+                Ok(value) => match value {
+                    google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA1 => {
+                        Ok(TokenAlgorithm::Sha1)
+                    }
+                    google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA256 => {
+                        Ok(TokenAlgorithm::Sha256)
+                    }
+                    google_auth::google_auth_backup::Algorithm::ALGORITHM_SHA512 => {
+                        Ok(TokenAlgorithm::Sha512)
+                    }
+                    google_auth::google_auth_backup::Algorithm::ALGORITHM_MD5 => {
+                        Ok(TokenAlgorithm::Md5)
+                    }
+                    _ => Err(Error::parse(format!(
+                        "Unsupported token algorithm: {:?}",
+                        value
+                    ))),
+                },
+                Err(_) => Err(Error::parse(format!(
+                    "Invalid token algorithm: {:?}",
+                    token.algorithm
+                ))),
+            }?;
+
+            let token_type = match token.type_.enum_value() {
+                Ok(value) => match value {
+                    google_auth::google_auth_backup::OtpType::OTP_TYPE_HOTP => Ok(TokenType::Hotp),
+                    google_auth::google_auth_backup::OtpType::OTP_TYPE_TOTP => Ok(TokenType::Totp),
+                    _ => Err(Error::parse(format!("Unsupported token type: {:?}", value))),
+                },
+                Err(_) => Err(Error::parse(format!(
+                    "Invalid token type: {:?}",
+                    token.type_
+                ))),
+            }?;
+
+            let digits = match token.digits.enum_value() {
+                Ok(value) => match value {
+                    google_auth::google_auth_backup::Digits::DIGITS_SIX => Ok(6),
+                    google_auth::google_auth_backup::Digits::DIGITS_EIGHT => Ok(8),
+                    _ => Err(Error::parse(format!(
+                        "Unsupported digits value: {:?}",
+                        value
+                    ))),
+                },
+                Err(_) => Err(Error::parse(format!(
+                    "Invalid digits value: {:?}",
+                    token.digits
+                ))),
+            }?;
+
+            let mut token = Token {
+                algorithm,
+                counter: token.counter as u32,
+                digits,
+                issuer: token.issuer,
+                label: token.name,
+                period: 30,
+                token_type,
+                key: token.secret.into(),
+                ..Default::default()
+            };
+
+            // Steam accounts are exported as TOTP with Steam issuer
+            if token.token_type == TokenType::Totp && is_steam_issuer(&token.issuer) {
+                token.set_type(TokenType::Steam);
+            }
+
+            Ok(token)
         })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use google_auth::google_auth_backup::{Algorithm, Digits, OtpParameters, OtpType};
+
+    fn otp_parameters(issuer: &str, otp_type: OtpType) -> OtpParameters {
+        let mut parameters = OtpParameters::new();
+        parameters.secret = b"12345678901234567890".to_vec();
+        parameters.name = "alice".to_owned();
+        parameters.issuer = issuer.to_owned();
+        parameters.algorithm = Algorithm::ALGORITHM_SHA1.into();
+        parameters.digits = Digits::DIGITS_SIX.into();
+        parameters.type_ = otp_type.into();
+        parameters
+    }
+
+    fn export_url(otp_parameters: Vec<OtpParameters>) -> String {
+        let mut backup = google_auth::GoogleAuthBackup::new();
+        backup.otp_parameters = otp_parameters;
+        let data =
+            base64::engine::general_purpose::STANDARD.encode(backup.write_to_bytes().unwrap());
+        format!(
+            "otpauth-migration://offline?data={}",
+            urlencoding::encode(&data)
+        )
+    }
+
+    #[test]
+    fn steam_issuer() {
+        let tokens = parse_export_url(&export_url(vec![
+            otp_parameters("Steam", OtpType::OTP_TYPE_TOTP),
+            otp_parameters("GitHub", OtpType::OTP_TYPE_TOTP),
+            otp_parameters("Steam", OtpType::OTP_TYPE_HOTP),
+        ]))
+        .unwrap();
+
+        assert_eq!(tokens[0].token_type, TokenType::Steam);
+        assert_eq!(tokens[0].digits, 5);
+        assert_eq!(tokens[1].token_type, TokenType::Totp);
+        assert_eq!(tokens[1].digits, 6);
+        assert_eq!(tokens[2].token_type, TokenType::Hotp);
+    }
 }
@@ -0,0 +1,301 @@
+use zeroize::Zeroizing;
+
+use super::is_steam_issuer;
+use crate::error::{Error, Result};
+use crate::otp::{MAX_DIGITS, MIN_DIGITS};
+use crate::types::{SecretBytes, Token, TokenAlgorithm, TokenType};
+
+const SCHEME: &str = "otpauth://";
+
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+/// Parse token url (`otpauth://totp/Issuer:account?secret=...`). Parameter names and values are
+/// case-insensitive, secret may be unpadded or lowercase, unknown parameters (e.g. `image`) are
+/// ignored. Steam tokens are recognized by `steam` type, `encoder=steam` parameter or `Steam`
+/// issuer, `motp` and `yaotp` types are mOTP and Yandex Key tokens, their PIN is not part of url
+pub fn parse_otpauth_url(url: &str) -> Result<Token> {
+    let url = url.trim();
+
+    let rest = url
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &url[SCHEME.len()..])
+        .ok_or_else(|| Error::parse("Token url must start with otpauth://"))?;
+
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (token_type, label) = path.split_once('/').unwrap_or((path, ""));
+
+    let token_type = match token_type.to_lowercase().as_str() {
+        "totp" => TokenType::Totp,
+        "hotp" => TokenType::Hotp,
+        "steam" => TokenType::Steam,
+        "motp" => TokenType::Motp,
+        "yaotp" => TokenType::Yandex,
+        "" => Err(Error::parse("Token type is missing in url"))?,
+        other => Err(Error::parse(format!("Unsupported token type: {}", other)))?,
+    };
+
+    let label = decode_component(label)?;
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (issuer.trim().to_owned(), account.trim().to_owned()),
+        None => (String::new(), label.trim().to_owned()),
+    };
+
+    let mut token = Token {
+        token_type,
+        digits: DEFAULT_DIGITS,
+        period: DEFAULT_PERIOD,
+        issuer: label_issuer,
+        label: account,
+        ..Default::default()
+    };
+    let mut secret = None;
+
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+
+        match name.to_lowercase().as_str() {
+            "secret" => secret = Some(decode_secret(value, token.token_type)?),
+            // Issuer parameter is preferred over label prefix, as recommended by spec
+            "issuer" => token.issuer = decode_query_value(value)?.trim().to_owned(),
+            "algorithm" => token.algorithm = parse_algorithm(value)?,
+            "digits" => {
+                token.digits = value
+                    .parse()
+                    .ok()
+                    .filter(|digits| (MIN_DIGITS..=MAX_DIGITS).contains(digits))
+                    .ok_or_else(|| Error::parse(format!("Invalid digits value: {}", value)))?
+            }
+            "period" => {
+                token.period = value
+                    .parse()
+                    .ok()
+                    .filter(|period| *period > 0)
+                    .ok_or_else(|| Error::parse(format!("Invalid period value: {}", value)))?
+            }
+            "counter" => {
+                token.counter = value
+                    .parse()
+                    .map_err(|_| Error::parse(format!("Invalid counter value: {}", value)))?
+            }
+            "encoder" if value.eq_ignore_ascii_case("steam") => token.token_type = TokenType::Steam,
+            // Vendor extensions (image, color, icon etc.) do not affect codes
+            _ => {}
+        }
+    }
+
+    token.key = secret.ok_or_else(|| Error::parse("Token url does not contain secret"))?;
+
+    if token.token_type == TokenType::Totp && is_steam_issuer(&token.issuer) {
+        token.token_type = TokenType::Steam;
+    }
+    token.set_type(token.token_type);
+
+    Ok(token)
+}
+
+/// Percent-decodes url component, `+` is kept as is since it is literal character in url path
+fn decode_component(component: &str) -> Result<String> {
+    urlencoding::decode(component)
+        .map(|decoded| decoded.into_owned())
+        .map_err(|err| Error::parse(format!("Invalid percent-encoding in url: {}", err)))
+}
+
+/// Percent-decodes query parameter value, `+` is treated as space since some apps encode issuer
+/// this way
+fn decode_query_value(value: &str) -> Result<String> {
+    decode_component(&value.replace('+', " "))
+}
+
+/// Decodes base32 secret (hex for mOTP tokens), padding, spaces and letter case are ignored
+fn decode_secret(value: &str, token_type: TokenType) -> Result<SecretBytes> {
+    let decoded = Zeroizing::new(
+        urlencoding::decode(value)
+            .map_err(|err| Error::parse(format!("Invalid percent-encoding in url: {}", err)))?
+            .into_owned(),
+    );
+    let normalized = Zeroizing::new(
+        decoded
+            .chars()
+            .filter(|ch| !ch.is_whitespace() && *ch != '=' && *ch != '-')
+            .collect::<String>()
+            .to_uppercase(),
+    );
+
+    if normalized.is_empty() {
+        Err(Error::parse("Token secret is empty"))?;
+    }
+
+    match token_type {
+        TokenType::Motp => hex::decode(normalized.as_str())
+            .map(SecretBytes::from)
+            .map_err(|err| Error::parse(format!("Token secret is not valid hex: {}", err))),
+        _ => base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &normalized)
+            .map(SecretBytes::from)
+            .ok_or_else(|| Error::parse("Token secret is not valid base32")),
+    }
+}
+
+fn parse_algorithm(value: &str) -> Result<TokenAlgorithm> {
+    match value.to_uppercase().replace('-', "").as_str() {
+        "SHA1" => Ok(TokenAlgorithm::Sha1),
+        "SHA256" => Ok(TokenAlgorithm::Sha256),
+        "SHA512" => Ok(TokenAlgorithm::Sha512),
+        "MD5" => Ok(TokenAlgorithm::Md5),
+        _ => Err(Error::parse(format!("Unsupported algorithm: {}", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn error(url: &str) -> String {
+        parse_otpauth_url(url).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn hotp_with_counter() {
+        let token = parse_otpauth_url(&format!(
+            "otpauth://hotp/alice?secret={}&counter=42",
+            SECRET
+        ))
+        .unwrap();
+
+        assert_eq!(token.token_type, TokenType::Hotp);
+        assert_eq!(token.counter, 42);
+        assert_eq!(token.digits, DEFAULT_DIGITS);
+        assert_eq!(&*token.key, b"12345678901234567890");
+    }
+
+    #[test]
+    fn mixed_case_parameters() {
+        let token = parse_otpauth_url(&format!(
+            "OTPAUTH://TOTP/alice?Secret={}&ALGORITHM=sha-256&Digits=8&PERIOD=60",
+            SECRET
+        ))
+        .unwrap();
+
+        assert_eq!(token.token_type, TokenType::Totp);
+        assert_eq!(token.algorithm, TokenAlgorithm::Sha256);
+        assert_eq!(token.digits, 8);
+        assert_eq!(token.period, 60);
+    }
+
+    #[test]
+    fn unpadded_lowercase_secret() {
+        let token = parse_otpauth_url("otpauth://totp/alice?secret=mzxw6").unwrap();
+
+        assert_eq!(&*token.key, b"foo");
+    }
+
+    #[test]
+    fn label_with_issuer() {
+        let token = parse_otpauth_url(&format!(
+            "otpauth://totp/Google%20Mail:alice+work%40gmail.com?secret={}",
+            SECRET
+        ))
+        .unwrap();
+
+        assert_eq!(token.issuer, "Google Mail");
+        assert_eq!(token.label, "alice+work@gmail.com");
+    }
+
+    #[test]
+    fn issuer_parameter_is_preferred() {
+        let token = parse_otpauth_url(&format!(
+            "otpauth://totp/Old:alice?secret={}&issuer=New+Issuer",
+            SECRET
+        ))
+        .unwrap();
+
+        assert_eq!(token.issuer, "New Issuer");
+        assert_eq!(token.label, "alice");
+    }
+
+    #[test]
+    fn steam() {
+        let by_encoder = parse_otpauth_url(&format!(
+            "otpauth://totp/Valve:alice?secret={}&encoder=steam",
+            SECRET
+        ))
+        .unwrap();
+        let by_issuer = parse_otpauth_url(&format!(
+            "otpauth://totp/alice?secret={}&issuer=Steam",
+            SECRET
+        ))
+        .unwrap();
+
+        for token in [by_encoder, by_issuer] {
+            assert_eq!(token.token_type, TokenType::Steam);
+            assert_eq!(token.digits, 5);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            error("https://example.com"),
+            "Token url must start with otpauth://"
+        );
+        assert_eq!(error("otpauth://"), "Token type is missing in url");
+        assert_eq!(
+            error(&format!("otpauth://foo/alice?secret={}", SECRET)),
+            "Unsupported token type: foo"
+        );
+        assert!(
+            error(&format!("otpauth://totp/alice%FF?secret={}", SECRET))
+                .starts_with("Invalid percent-encoding in url")
+        );
+        assert!(
+            error(&format!(
+                "otpauth://totp/alice?secret={}&issuer=%FF",
+                SECRET
+            ))
+            .starts_with("Invalid percent-encoding in url")
+        );
+        assert!(
+            error("otpauth://totp/alice?secret=%FF").starts_with("Invalid percent-encoding in url")
+        );
+        assert_eq!(
+            error(&format!(
+                "otpauth://totp/alice?secret={}&algorithm=md4",
+                SECRET
+            )),
+            "Unsupported algorithm: md4"
+        );
+        assert_eq!(
+            error(&format!("otpauth://totp/alice?secret={}&digits=3", SECRET)),
+            "Invalid digits value: 3"
+        );
+        assert_eq!(
+            error(&format!("otpauth://totp/alice?secret={}&period=0", SECRET)),
+            "Invalid period value: 0"
+        );
+        assert_eq!(
+            error(&format!(
+                "otpauth://hotp/alice?secret={}&counter=-1",
+                SECRET
+            )),
+            "Invalid counter value: -1"
+        );
+        assert_eq!(
+            error("otpauth://totp/alice?issuer=Foo"),
+            "Token url does not contain secret"
+        );
+        assert_eq!(
+            error("otpauth://totp/alice?secret=="),
+            "Token secret is empty"
+        );
+        assert_eq!(
+            error("otpauth://totp/alice?secret=1111"),
+            "Token secret is not valid base32"
+        );
+        assert!(
+            error("otpauth://motp/alice?secret=xyz").starts_with("Token secret is not valid hex")
+        );
+    }
+}
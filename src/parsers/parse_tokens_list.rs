@@ -1,37 +1,11 @@
-use anyhow::{Result, anyhow};
-use std::{fs, path::PathBuf};
-use totp_rs::{Algorithm, TOTP};
+use std::path::Path;
 
-use crate::types::{Token, TokenAlgorithm, TokenType};
+use super::{ParsedBackup, parse_lines, parse_otpauth_url};
+use crate::error::Result;
 
 /// Parse file with token urls (`otpauth://...`), one url on each line
-pub fn parse_tokens_list(backup_file: &PathBuf) -> Result<Vec<Token>> {
-    fs::read_to_string(backup_file)?
-        .trim()
-        .lines()
-        .map(|row| {
-            let token =
-                TOTP::from_url_unchecked(row.trim().replace("algorithm=sha", "algorithm=SHA"))
-                    .map_err(|err| anyhow!("Failed to parse token from url: {}", err))?;
-
-            let algorithm = match token.algorithm {
-                Algorithm::SHA1 => TokenAlgorithm::Sha1,
-                Algorithm::SHA256 => TokenAlgorithm::Sha256,
-                Algorithm::SHA512 => TokenAlgorithm::Sha512,
-            };
-
-            let token_type = TokenType::Totp;
-
-            Ok(Token {
-                algorithm,
-                counter: 0,
-                digits: token.digits as u32,
-                issuer: token.issuer.unwrap_or_default(),
-                label: token.account_name,
-                period: token.step,
-                token_type,
-                key: token.secret,
-            })
-        })
-        .collect()
+pub fn parse_tokens_list(backup_file: &Path, skip_invalid: bool) -> Result<ParsedBackup> {
+    parse_lines(backup_file, skip_invalid, |row| {
+        Ok(vec![parse_otpauth_url(row)?])
+    })
 }
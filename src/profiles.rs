@@ -0,0 +1,78 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use crate::{
+    agent::cached_vault,
+    error::Error,
+    prompt::{ask_credentials, ask_profile_credentials},
+    vault::Vault,
+};
+
+/// Database opened in session. Profile is set only when several databases are opened, then their
+/// tokens are told apart by `profile/name`
+pub struct Database {
+    pub profile: Option<String>,
+    pub path: PathBuf,
+}
+
+impl Database {
+    pub fn new(profile: Option<String>, path: PathBuf) -> Self {
+        Self { profile, path }
+    }
+
+    /// Token name shown to user, prefixed with profile if it is set
+    pub fn token_name(&self, name: &str) -> String {
+        match &self.profile {
+            Some(profile) => format!("{}/{}", profile, name),
+            None => name.to_owned(),
+        }
+    }
+
+    /// Asks for credentials, prompt names profile if it is set
+    pub fn open(&self) -> Result<Vault> {
+        Ok(match &self.profile {
+            Some(profile) => Vault::open(&self.path, ask_profile_credentials(profile))?,
+            None => Vault::open(&self.path, ask_credentials)?,
+        })
+    }
+
+    /// Opens database with key cached by agent, asks for credentials if agent does not know it
+    pub fn open_cached(&self) -> Result<Vault> {
+        match cached_vault(&self.path) {
+            Some(vault) => Ok(vault),
+            None => self.open(),
+        }
+    }
+
+    /// Opens database without decrypting token keys, see [`Vault::open_metadata`]
+    pub fn open_metadata(&self) -> Result<Vault> {
+        Ok(match &self.profile {
+            Some(profile) => Vault::open_metadata(&self.path, ask_profile_credentials(profile))?,
+            None => Vault::open_metadata(&self.path, ask_credentials)?,
+        })
+    }
+}
+
+/// Finds database of token referenced as `profile/name`, profile may be omitted when single
+/// database is opened
+pub fn find_token<'a, 'b>(
+    databases: &'a [Database],
+    name: &'b str,
+) -> Result<(&'a Database, &'b str)> {
+    if let [database] = databases
+        && database.profile.is_none()
+    {
+        return Ok((database, name));
+    }
+
+    let found = name.split_once('/').and_then(|(profile, token_name)| {
+        databases
+            .iter()
+            .find(|database| database.profile.as_deref() == Some(profile))
+            .map(|database| (database, token_name))
+    });
+
+    Ok(found.ok_or_else(|| {
+        Error::InvalidArgument(format!("Token {} must be referenced as profile/name", name))
+    })?)
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::{self, Write, stdin, stdout};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU16, Ordering};
+use termion::input::TermRead;
+
+use crate::{
+    challenge_response,
+    error::{Error, Result},
+    types::{SecretBytes, SecretString, UnlockFactors},
+    vault::Credentials,
+};
+
+/// Key file from command line or config file, it is asked only if not set
+static KEY_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Lines printed by prompts since last [`take_prompted_lines`], so full-screen modes can erase them
+static PROMPTED_LINES: AtomicU16 = AtomicU16::new(0);
+
+pub fn set_key_file(path: PathBuf) {
+    KEY_FILE.set(path).ok();
+}
+
+/// Amount of prompt lines printed since previous call
+pub fn take_prompted_lines() -> u16 {
+    PROMPTED_LINES.swap(0, Ordering::Relaxed)
+}
+
+/// Asks for password on terminal without echoing it
+pub fn read_password(prompt: &str) -> Result<SecretString> {
+    PROMPTED_LINES.fetch_add(1, Ordering::Relaxed);
+    let stdout = stdout();
+    let mut stdout = stdout.lock();
+    let stdin = stdin();
+    let mut stdin = stdin.lock();
+
+    stdout.write_all(prompt.as_bytes())?;
+    stdout.flush()?;
+    let password = stdin
+        .read_passwd(&mut stdout)?
+        .ok_or_else(|| aborted("Password input aborted"))?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+
+    Ok(SecretString::from(password))
+}
+
+/// Asks for single line of input, trailing newline is stripped
+pub fn read_line(prompt: &str) -> Result<String> {
+    PROMPTED_LINES.fetch_add(1, Ordering::Relaxed);
+    let stdout = stdout();
+    let mut stdout = stdout.lock();
+
+    stdout.write_all(prompt.as_bytes())?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer)? == 0 {
+        Err(aborted("Input aborted"))?;
+    }
+
+    Ok(answer.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Asks yes/no question, anything except `y` or `yes` counts as refusal
+pub fn confirm(prompt: &str) -> Result<bool> {
+    let answer = read_line(&format!("{} [y/N]: ", prompt))?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Asks for database password and secrets of other unlock factors database requires
+pub fn ask_credentials(unlock_factors: &UnlockFactors) -> Result<Credentials> {
+    ask_credentials_with_prompt("Enter database password: ", unlock_factors)
+}
+
+/// Same as [`ask_credentials`], but names profile of database, so it's clear which password is
+/// asked when several databases are opened
+pub fn ask_profile_credentials(
+    profile: &str,
+) -> impl FnOnce(&UnlockFactors) -> Result<Credentials> + '_ {
+    move |unlock_factors| {
+        ask_credentials_with_prompt(
+            &format!("Enter password of {} database: ", profile),
+            unlock_factors,
+        )
+    }
+}
+
+fn ask_credentials_with_prompt(
+    prompt: &str,
+    unlock_factors: &UnlockFactors,
+) -> Result<Credentials> {
+    let password = read_password(prompt)?;
+
+    let key_file = match (unlock_factors.key_file, KEY_FILE.get()) {
+        (true, Some(path)) => Some(read_key_file(path)?),
+        (true, None) => Some(read_key_file(Path::new(
+            read_line("Enter path to key file: ")?.trim(),
+        ))?),
+        (false, _) => None,
+    };
+
+    let challenge_response = unlock_factors
+        .challenge_response
+        .as_ref()
+        .map(challenge_response::respond)
+        .transpose()?;
+
+    Ok(Credentials {
+        password,
+        key_file,
+        challenge_response,
+    })
+}
+
+pub fn read_key_file(path: &Path) -> Result<SecretBytes> {
+    let contents = fs::read(path).map_err(|err| {
+        Error::CredentialsRequired(format!(
+            "Failed to read key file {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    Ok(SecretBytes::from(contents))
+}
+
+fn aborted(message: &str) -> Error {
+    Error::Terminal(io::Error::new(io::ErrorKind::UnexpectedEof, message))
+}
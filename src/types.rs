@@ -1,45 +1,117 @@
-use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-mod buffered_stdout;
+mod secret;
 
-pub use buffered_stdout::BufferedStdout;
+pub use secret::{SecretBytes, SecretString};
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-pub enum BackupType {
-    TokensList,
-    FreeOtp,
-    GoogleAuth,
-}
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Hash function used for HMAC in code generation
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenAlgorithm {
+    #[default]
     Sha1,
     Sha256,
     Sha512,
+    /// HMAC-MD5, used by some legacy tokens
+    Md5,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TokenType {
+    /// Counter based (RFC 4226)
     Hotp,
+    /// Time based (RFC 6238)
+    #[default]
     Totp,
+    /// Steam Guard, time based with 5 character codes from Steam alphabet
+    Steam,
+    /// Mobile-OTP, MD5 of time, secret and PIN, new 6 hex digits code every 10 seconds
+    Motp,
+    /// Yandex Key, HMAC-SHA256 keyed with PIN and secret, 8 letter codes
+    Yandex,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Token stored in database, tokens are identified by their names which are keys of
+/// [`KeyFile::tokens`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Token {
     pub algorithm: TokenAlgorithm,
+    /// Next counter value for HOTP tokens
     pub counter: u32,
+    /// Amount of digits in code
     pub digits: u32,
     pub issuer: String,
+    /// Account name
     pub label: String,
+    /// Code validity period in seconds for TOTP tokens
     pub period: u64,
+    /// Unix time periods are counted from (T0 of RFC 6238)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub t0: u64,
     pub token_type: TokenType,
-    pub key: Vec<u8>,
+    /// Encrypted key (see [`crate::Vault::decrypt_secret`]), raw key in tokens returned by parsers
+    pub key: SecretBytes,
+    /// Encrypted PIN of mOTP and Yandex tokens, encrypted the same way as key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<SecretBytes>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// Position in token lists, tokens without it go after ordered ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_order: Option<i64>,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Additional unlock factors, their secrets are mixed into master key derivation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UnlockFactors {
+    /// Contents of key file (usually stored on removable media) are required
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub key_file: bool,
+    /// Response of helper command (hardware token etc.) to stored challenge is required, helper is
+    /// configured by user and is never read from database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge_response: Option<ChallengeResponse>,
+}
+
+/// Challenge-response unlock factor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    /// Helper command written by older versions. It is never run, since database is not
+    /// authenticated before unlocking, and is kept only so MAC of such files verifies. It is
+    /// dropped on next save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub challenge: Vec<u8>,
 }
 
+/// Database file contents, see [`crate::Vault`] for unlocked database
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyFile {
+    /// File format version, files written before versioning was introduced have version 1
+    #[serde(default = "KeyFile::legacy_version")]
+    pub version: u32,
+    /// Salt for master key derivation
     pub master_key_salt: Vec<u8>,
+    /// Secrets required to unlock database in addition to password
+    #[serde(default, skip_serializing_if = "UnlockFactors::is_empty")]
+    pub unlock_factors: UnlockFactors,
+    /// Tokens with encrypted keys, empty while tokens are sealed
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub tokens: BTreeMap<String, Token>,
+    /// Whole tokens map encrypted with master key, nonce is prepended to ciphertext
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed_tokens: Option<Vec<u8>>,
+    /// HMAC over the rest of file, detects tampering with tokens or header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<Vec<u8>>,
 }
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+#[cfg(unix)]
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Byte buffer holding key material: wiped on drop, locked in memory where the platform allows it
+/// and never printed by `Debug`
+pub struct SecretBytes(Box<[u8]>);
+
+impl SecretBytes {
+    /// Takes bytes over, source buffer is wiped
+    pub fn new(mut bytes: Vec<u8>) -> Self {
+        // Copy instead of `into_boxed_slice` which may reallocate and leave unwiped data behind
+        let secret = Self(bytes.as_slice().into());
+        bytes.zeroize();
+        lock_memory(&secret.0);
+        secret
+    }
+
+    /// Buffer of given length filled with zeros, to be filled in place
+    pub fn zeroed(len: usize) -> Self {
+        Self::new(vec![0u8; len])
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl Default for SecretBytes {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::new(self.0.to_vec())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        self.0.zeroize();
+        unlock_memory(&self.0);
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.0.len())
+    }
+}
+
+impl Serialize for SecretBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.as_ref().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// String holding a password: wiped on drop and never printed by `Debug`
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString([REDACTED])")
+    }
+}
+
+/// Lock counts of pages holding secrets. `mlock` works on whole pages and locks don't nest, so
+/// page is unlocked only when the last secret on it is dropped
+#[cfg(unix)]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+#[cfg(unix)]
+fn lock_memory(bytes: &[u8]) {
+    let mut locked_pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+
+    for page in pages(bytes) {
+        let count = locked_pages.entry(page).or_insert(0);
+        if *count == 0 {
+            // Failure is not fatal: RLIMIT_MEMLOCK may be too low, data is still wiped on drop
+            unsafe {
+                libc::mlock(page as *const libc::c_void, page_size());
+            }
+        }
+        *count += 1;
+    }
+}
+
+#[cfg(unix)]
+fn unlock_memory(bytes: &[u8]) {
+    let mut locked_pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+
+    for page in pages(bytes) {
+        let Some(count) = locked_pages.get_mut(&page) else {
+            continue;
+        };
+        *count -= 1;
+        if *count == 0 {
+            locked_pages.remove(&page);
+            unsafe {
+                libc::munlock(page as *const libc::c_void, page_size());
+            }
+        }
+    }
+}
+
+/// Start addresses of pages buffer spans, none for empty buffer
+#[cfg(unix)]
+fn pages(bytes: &[u8]) -> impl Iterator<Item = usize> {
+    let page_size = page_size();
+    let start = bytes.as_ptr() as usize;
+    let first = start - start % page_size;
+    let end = start + bytes.len();
+
+    (first..end)
+        .step_by(page_size)
+        .take_while(move |_| !bytes.is_empty())
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE.get_or_init(|| unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize)
+}
+
+#[cfg(not(unix))]
+fn lock_memory(_bytes: &[u8]) {}
+
+#[cfg(not(unix))]
+fn unlock_memory(_bytes: &[u8]) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn lock_count(bytes: &[u8]) -> Option<usize> {
+        let page = pages(bytes).next().unwrap();
+        LOCKED_PAGES.lock().unwrap().get(&page).copied()
+    }
+
+    #[test]
+    fn page_stays_locked_while_any_secret_on_it_lives() {
+        // Two secrets in one buffer are guaranteed to share page, unlike separate allocations
+        let buffer = [1u8; 64];
+        let (first, second) = buffer.split_at(32);
+
+        lock_memory(first);
+        lock_memory(second);
+        let both = lock_count(first);
+        unlock_memory(first);
+        let one = lock_count(second);
+        unlock_memory(second);
+
+        assert_eq!(both, Some(2));
+        assert_eq!(one, Some(1));
+        assert_eq!(lock_count(second), None);
+    }
+
+    #[test]
+    fn secret_spanning_pages() {
+        let buffer = vec![1u8; page_size() * 2];
+        let secret = &buffer[page_size() / 2..page_size() * 3 / 2];
+
+        assert_eq!(pages(secret).count(), 2);
+        assert_eq!(pages(&buffer[..0]).count(), 0);
+    }
+}
@@ -0,0 +1,726 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, OsRng, Payload},
+};
+use hmac::{Hmac, Mac};
+use rand::{self, Rng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::error::{Error, Result};
+use crate::otp::Generator;
+use crate::types::{
+    ChallengeResponse, KeyFile, SecretBytes, SecretString, Token, TokenType, UnlockFactors,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 32;
+const CHALLENGE_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 24;
+
+/// Tokens stored in plain TOML, only token keys are encrypted
+const LEGACY_PLAIN_VERSION: u32 = 1;
+/// Whole tokens map is encrypted, only header (version and salt) is stored in plain text
+const LEGACY_SEALED_VERSION: u32 = 2;
+/// Token keys are bound to token name and parameters, whole file is authenticated with MAC,
+/// tokens may be sealed or not
+const CURRENT_VERSION: u32 = 3;
+
+/// Context for deriving MAC key from master key, so the same key is not used for two purposes
+const MAC_KEY_CONTEXT: &[u8] = b"otp-cli key file mac";
+
+/// Contents of sealed tokens blob
+#[derive(Serialize, Deserialize)]
+struct SealedTokens {
+    tokens: BTreeMap<String, Token>,
+}
+
+impl KeyFile {
+    /// Reads database from disk without decrypting anything
+    pub fn load(path: &Path) -> Result<Self> {
+        let key_file_data = fs::read_to_string(path)?;
+        let key_file = toml::from_str::<KeyFile>(key_file_data.as_str())?;
+
+        match key_file.version {
+            LEGACY_PLAIN_VERSION => {}
+            LEGACY_SEALED_VERSION if key_file.sealed_tokens.is_some() => {}
+            LEGACY_SEALED_VERSION => Err(Error::CorruptDatabase(
+                "sealed database does not contain tokens".to_owned(),
+            ))?,
+            CURRENT_VERSION if key_file.mac.is_some() => {}
+            CURRENT_VERSION => Err(Error::CorruptDatabase(
+                "integrity check is missing".to_owned(),
+            ))?,
+            version => Err(Error::UnsupportedFormat(format!(
+                "database version {}, update the app to open this file",
+                version
+            )))?,
+        }
+
+        Ok(key_file)
+    }
+
+    /// Writes database as is, use [`Vault::save`] to save unlocked database
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let serialized_file = toml::to_string(self)?;
+        fs::write(path, serialized_file)?;
+        Ok(())
+    }
+
+    /// Tokens in display order: favorites first, then by sort order, then by name
+    pub fn sorted_tokens(&self) -> Vec<(&String, &Token)> {
+        let mut tokens = self.tokens.iter().collect::<Vec<_>>();
+        tokens.sort_by_key(|(name, token)| {
+            (
+                !token.favorite,
+                token.sort_order.is_none(),
+                token.sort_order,
+                name.to_owned(),
+            )
+        });
+        tokens
+    }
+
+    /// Tokens with given tag in display order, fails if no token has it
+    pub fn tagged_tokens(&self, tag: &str) -> Result<Vec<(&String, &Token)>> {
+        let tokens = self
+            .sorted_tokens()
+            .into_iter()
+            .filter(|(_, token)| token.tags.iter().any(|token_tag| token_tag == tag))
+            .collect::<Vec<_>>();
+
+        match tokens.is_empty() {
+            true => Err(Error::TagNotFound(tag.to_owned())),
+            false => Ok(tokens),
+        }
+    }
+
+    /// Creates empty database with random master key salt
+    pub fn with_random_salt() -> Self {
+        KeyFile {
+            version: CURRENT_VERSION,
+            master_key_salt: random_bytes(SALT_LENGTH),
+            unlock_factors: UnlockFactors::default(),
+            tokens: BTreeMap::new(),
+            sealed_tokens: None,
+            mac: None,
+        }
+    }
+
+    pub(crate) fn legacy_version() -> u32 {
+        LEGACY_PLAIN_VERSION
+    }
+
+    /// Token metadata is encrypted and can not be read without password
+    pub fn is_sealed(&self) -> bool {
+        self.sealed_tokens.is_some()
+    }
+
+    /// Header data authenticated along with sealed tokens, so salt or version can't be swapped
+    fn sealed_header(&self, version: u32) -> Vec<u8> {
+        let mut header = version.to_le_bytes().to_vec();
+        header.extend_from_slice(&self.master_key_salt);
+        header
+    }
+
+    /// Data MAC is calculated over: whole file except MAC itself, in canonical form so that
+    /// formatting changes don't break it
+    fn mac_input(&self) -> Result<Zeroizing<String>> {
+        Ok(Zeroizing::new(toml::to_string(&KeyFile {
+            mac: None,
+            ..self.clone()
+        })?))
+    }
+}
+
+impl Token {
+    /// Changes token type, digits and period are changed too for types which codes always have
+    /// the same shape
+    pub fn set_type(&mut self, token_type: TokenType) {
+        self.token_type = token_type;
+        if let Some((digits, period)) = token_type.fixed_shape() {
+            self.digits = digits;
+            self.period = period;
+        }
+    }
+}
+
+impl TokenType {
+    /// Digits and period of types that don't allow to change them
+    pub fn fixed_shape(&self) -> Option<(u32, u64)> {
+        match self {
+            TokenType::Steam => Some((5, 30)),
+            TokenType::Motp => Some((6, 10)),
+            TokenType::Yandex => Some((8, 30)),
+            TokenType::Hotp | TokenType::Totp => None,
+        }
+    }
+
+    /// Codes of this type depend on PIN stored along with token secret
+    pub fn requires_pin(&self) -> bool {
+        matches!(self, TokenType::Motp | TokenType::Yandex)
+    }
+}
+
+impl UnlockFactors {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl ChallengeResponse {
+    /// Creates challenge-response factor with new random challenge
+    pub fn with_random_challenge() -> Self {
+        Self {
+            command: None,
+            challenge: random_bytes(CHALLENGE_LENGTH),
+        }
+    }
+}
+
+/// Secrets database is unlocked with
+pub struct Credentials {
+    pub password: SecretString,
+    /// Contents of key file, if database requires it
+    pub key_file: Option<SecretBytes>,
+    /// Response of challenge-response helper, if database requires it
+    pub challenge_response: Option<SecretBytes>,
+}
+
+pub fn random_bytes(length: usize) -> Vec<u8> {
+    let mut rng: StdRng = rand::make_rng();
+    let mut bytes = vec![0u8; length];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Derives master key from password and secrets of additional unlock factors required by database,
+/// factor secrets are hashed, so key file of any size can be used
+fn derive_key(key_file: &KeyFile, credentials: &Credentials) -> Result<SecretBytes> {
+    let factors = &key_file.unlock_factors;
+    let mut input = Zeroizing::new(credentials.password.as_bytes().to_vec());
+
+    match (factors.key_file, &credentials.key_file) {
+        (true, Some(key_file)) => input.extend_from_slice(&Sha256::digest(&key_file[..])),
+        (true, None) => Err(Error::CredentialsRequired(
+            "Key file is required to unlock database".to_owned(),
+        ))?,
+        (false, _) => {}
+    }
+
+    match (&factors.challenge_response, &credentials.challenge_response) {
+        (Some(_), Some(response)) => input.extend_from_slice(&Sha256::digest(&response[..])),
+        (Some(_), None) => Err(Error::CredentialsRequired(
+            "Challenge-response is required to unlock database".to_owned(),
+        ))?,
+        (None, _) => {}
+    }
+
+    let mut encryption_key = SecretBytes::zeroed(KEY_LENGTH);
+    Argon2::default()
+        .hash_password_into(
+            &input,
+            key_file.master_key_salt.as_slice(),
+            encryption_key.as_mut_slice(),
+        )
+        .map_err(|err| Error::Crypto(err.to_string()))?;
+
+    Ok(encryption_key)
+}
+
+/// Associated data for token key encryption, ties encrypted key to token name and parameters that
+/// affect generated codes, so keys can't be swapped between tokens and parameters can't be changed
+/// without password
+fn token_associated_data(name: &str, token: &Token) -> Vec<u8> {
+    format!("{}\0{}", name, token_parameters(token)).into_bytes()
+}
+
+/// Code generation parameters, T0 is included only when set, so keys encrypted before it was
+/// introduced stay valid
+fn token_parameters(token: &Token) -> String {
+    let mut parameters = format!(
+        "{:?}:{}:{}:{:?}",
+        token.algorithm, token.digits, token.period, token.token_type
+    );
+    if token.t0 != 0 {
+        parameters.push_str(&format!(":{}", token.t0));
+    }
+    parameters
+}
+
+/// Associated data for PIN encryption, differs from secret one so PIN and secret can't be swapped
+fn pin_associated_data(name: &str, token: &Token) -> Vec<u8> {
+    let mut associated_data = token_associated_data(name, token);
+    associated_data.extend_from_slice(b"\0pin");
+    associated_data
+}
+
+/// Hash identifying token by its secret, code generation parameters and PIN (for types that use
+/// it), tokens with equal fingerprints produce the same codes
+pub fn token_fingerprint(token: &Token, secret: &[u8], pin: Option<&[u8]>) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(secret);
+    hasher.update(token_parameters(token));
+    if token.token_type.requires_pin() {
+        let pin = pin.unwrap_or_default();
+        hasher.update(b"\0pin");
+        hasher.update((pin.len() as u64).to_be_bytes());
+        hasher.update(pin);
+    }
+    hasher.finalize().into()
+}
+
+/// Database unlocked with master password (and additional unlock factors), decrypts token keys and
+/// keeps tokens sealed on save if they were sealed
+pub struct Vault {
+    path: PathBuf,
+    /// Database with unsealed tokens
+    pub key_file: KeyFile,
+    /// Not set when database was opened only for metadata access
+    encryption_key: Option<SecretBytes>,
+    /// Tokens are sealed back when database is saved
+    sealed: bool,
+}
+
+impl Vault {
+    /// Opens existing database, `ask_credentials` is called with unlock factors database requires
+    pub fn open(
+        path: &Path,
+        ask_credentials: impl FnOnce(&UnlockFactors) -> Result<Credentials>,
+    ) -> Result<Self> {
+        let key_file = KeyFile::load(path)?;
+        let credentials = ask_credentials(&key_file.unlock_factors)?;
+        Self::unlock(path, key_file, &credentials)
+    }
+
+    /// Opens database or creates new one (protected by password only) if file does not exist yet
+    pub fn open_or_create(
+        path: &Path,
+        ask_credentials: impl FnOnce(&UnlockFactors) -> Result<Credentials>,
+    ) -> Result<Self> {
+        let key_file = match path.exists() {
+            true => KeyFile::load(path)?,
+            false => KeyFile::with_random_salt(),
+        };
+        let credentials = ask_credentials(&key_file.unlock_factors)?;
+        Self::unlock(path, key_file, &credentials)
+    }
+
+    /// Opens database for reading token metadata, credentials are asked (by calling
+    /// `ask_credentials`) only if metadata is sealed, token keys can't be decrypted without them
+    pub fn open_metadata(
+        path: &Path,
+        ask_credentials: impl FnOnce(&UnlockFactors) -> Result<Credentials>,
+    ) -> Result<Self> {
+        let key_file = KeyFile::load(path)?;
+
+        match key_file.is_sealed() {
+            true => {
+                let credentials = ask_credentials(&key_file.unlock_factors)?;
+                Self::unlock(path, key_file, &credentials)
+            }
+            false => Ok(Self {
+                path: path.to_owned(),
+                key_file,
+                encryption_key: None,
+                sealed: false,
+            }),
+        }
+    }
+
+    fn unlock(path: &Path, key_file: KeyFile, credentials: &Credentials) -> Result<Self> {
+        let encryption_key = derive_key(&key_file, credentials)?;
+        Self::open_with_key(path, key_file, encryption_key)
+    }
+
+    /// Unlocks loaded database with master key derived earlier (e.g. cached by agent), fails if key
+    /// does not match database
+    pub fn open_with_key(
+        path: &Path,
+        mut key_file: KeyFile,
+        encryption_key: SecretBytes,
+    ) -> Result<Self> {
+        let legacy = key_file.version < CURRENT_VERSION;
+        let mac = key_file.mac.take();
+
+        let mut vault = Self {
+            path: path.to_owned(),
+            sealed: key_file.is_sealed(),
+            key_file,
+            encryption_key: Some(encryption_key),
+        };
+
+        if let Some(mac) = mac {
+            vault
+                .mac()?
+                .chain_update(vault.key_file.mac_input()?.as_bytes())
+                .verify_slice(&mac)
+                .map_err(|_| Error::WrongPassword)?;
+        }
+
+        if let Some(challenge_response) = &mut vault.key_file.unlock_factors.challenge_response {
+            challenge_response.command = None;
+        }
+
+        if let Some(sealed_tokens) = vault.key_file.sealed_tokens.take() {
+            vault.key_file.tokens = vault.unseal_tokens(&sealed_tokens)?;
+        }
+
+        // Legacy keys were encrypted without associated data, they are bound to tokens now and
+        // written in new format on next save
+        if legacy {
+            let names = vault.key_file.tokens.keys().cloned().collect::<Vec<_>>();
+            for name in names {
+                let token = &vault.key_file.tokens[&name];
+                let secret = vault.decrypt_key(&token.key, &[])?;
+                let key = vault.encrypt_secret(&name, token, &secret)?;
+                vault.key_file.tokens.get_mut(&name).unwrap().key = key;
+            }
+        }
+        vault.key_file.version = CURRENT_VERSION;
+
+        Ok(vault)
+    }
+
+    /// Writes database back to file it was opened from, tokens are sealed if database is sealed
+    pub fn save(&self) -> Result<()> {
+        let mut key_file = match self.sealed {
+            true => KeyFile {
+                version: CURRENT_VERSION,
+                master_key_salt: self.key_file.master_key_salt.clone(),
+                unlock_factors: self.key_file.unlock_factors.clone(),
+                tokens: BTreeMap::new(),
+                sealed_tokens: Some(self.seal_tokens()?),
+                mac: None,
+            },
+            false => KeyFile {
+                version: CURRENT_VERSION,
+                sealed_tokens: None,
+                mac: None,
+                ..self.key_file.clone()
+            },
+        };
+
+        key_file.mac = Some(
+            self.mac()?
+                .chain_update(key_file.mac_input()?.as_bytes())
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        );
+
+        key_file.save(&self.path)
+    }
+
+    pub fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Changes how tokens are stored on next save, requires password
+    pub fn set_sealed(&mut self, sealed: bool) -> Result<()> {
+        if self.encryption_key.is_none() {
+            Err(Error::CredentialsRequired(
+                "Database password is required to seal or unseal tokens".to_owned(),
+            ))?;
+        }
+        self.sealed = sealed;
+        Ok(())
+    }
+
+    fn seal_tokens(&self) -> Result<Vec<u8>> {
+        let serialized_tokens = Zeroizing::new(toml::to_string(&SealedTokens {
+            tokens: self.key_file.tokens.clone(),
+        })?);
+
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut ciphertext = self
+            .cipher()?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: serialized_tokens.as_bytes(),
+                    aad: &self.key_file.sealed_header(CURRENT_VERSION),
+                },
+            )
+            .map_err(|err| Error::Crypto(err.to_string()))?;
+
+        let mut sealed_tokens = nonce.to_vec();
+        sealed_tokens.append(&mut ciphertext);
+        Ok(sealed_tokens)
+    }
+
+    fn unseal_tokens(&self, sealed_tokens: &[u8]) -> Result<BTreeMap<String, Token>> {
+        if sealed_tokens.len() < NONCE_LENGTH {
+            Err(Error::CorruptDatabase(
+                "sealed tokens are too short".to_owned(),
+            ))?;
+        }
+
+        let serialized_tokens = Zeroizing::new(
+            self.cipher()?
+                .decrypt(
+                    XNonce::from_slice(&sealed_tokens[0..NONCE_LENGTH]),
+                    Payload {
+                        msg: &sealed_tokens[NONCE_LENGTH..],
+                        aad: &self.key_file.sealed_header(self.key_file.version),
+                    },
+                )
+                .map_err(|_| Error::WrongPassword)?,
+        );
+
+        let serialized_tokens = std::str::from_utf8(&serialized_tokens)
+            .map_err(|err| Error::CorruptDatabase(err.to_string()))?;
+        Ok(toml::from_str::<SealedTokens>(serialized_tokens)?.tokens)
+    }
+
+    /// Master key derived from credentials, can be cached to open database without asking for them
+    pub fn encryption_key(&self) -> Result<&SecretBytes> {
+        self.encryption_key.as_ref().ok_or_else(|| {
+            Error::CredentialsRequired(
+                "Database password is required to decrypt token keys".to_owned(),
+            )
+        })
+    }
+
+    fn cipher(&self) -> Result<XChaCha20Poly1305> {
+        XChaCha20Poly1305::new_from_slice(self.encryption_key()?)
+            .map_err(|err| Error::Crypto(err.to_string()))
+    }
+
+    /// HMAC keyed with subkey derived from master key
+    fn mac(&self) -> Result<HmacSha256> {
+        let mac_key = SecretBytes::from(
+            <HmacSha256 as Mac>::new_from_slice(self.encryption_key()?)
+                .map_err(|err| Error::Crypto(err.to_string()))?
+                .chain_update(MAC_KEY_CONTEXT)
+                .finalize()
+                .into_bytes()
+                .to_vec(),
+        );
+        <HmacSha256 as Mac>::new_from_slice(&mac_key).map_err(|err| Error::Crypto(err.to_string()))
+    }
+
+    /// Encrypts secret of token with given name, nonce is prepended to ciphertext. Token must be
+    /// re-encrypted if its name or parameters change, see `rename_token` and `update_token`
+    pub fn encrypt_secret(&self, name: &str, token: &Token, secret: &[u8]) -> Result<SecretBytes> {
+        self.encrypt_key(secret, &token_associated_data(name, token))
+    }
+
+    /// Encrypts PIN of token with given name, it is bound to token the same way as secret
+    pub fn encrypt_pin(&self, name: &str, token: &Token, pin: &[u8]) -> Result<SecretBytes> {
+        self.encrypt_key(pin, &pin_associated_data(name, token))
+    }
+
+    fn encrypt_key(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<SecretBytes> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut key = self
+            .cipher()?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|err| Error::Crypto(err.to_string()))?;
+        let mut encrypted_key = Vec::new();
+        encrypted_key.extend_from_slice(nonce.as_slice());
+        encrypted_key.append(&mut key);
+        Ok(encrypted_key.into())
+    }
+
+    /// Decrypts key of token with given name
+    pub fn decrypt_secret(&self, name: &str, token: &Token) -> Result<SecretBytes> {
+        self.decrypt_key(&token.key, &token_associated_data(name, token))
+    }
+
+    /// Decrypts PIN of token with given name, if token has it
+    pub fn decrypt_pin(&self, name: &str, token: &Token) -> Result<Option<SecretBytes>> {
+        token
+            .pin
+            .as_ref()
+            .map(|pin| self.decrypt_key(pin, &pin_associated_data(name, token)))
+            .transpose()
+    }
+
+    /// Encrypts raw key and PIN of token (as returned by parsers) in place
+    pub fn encrypt_token(&self, name: &str, token: &mut Token) -> Result<()> {
+        let secret = std::mem::take(&mut token.key);
+        let pin = token.pin.take();
+        self.reencrypt_token(name, token, &secret, pin.as_deref())
+    }
+
+    /// Re-encrypts secret and PIN of token after its name or parameters changed
+    fn reencrypt_token(
+        &self,
+        name: &str,
+        token: &mut Token,
+        secret: &[u8],
+        pin: Option<&[u8]>,
+    ) -> Result<()> {
+        token.key = self.encrypt_secret(name, token, secret)?;
+        token.pin = pin
+            .map(|pin| self.encrypt_pin(name, token, pin))
+            .transpose()?;
+        Ok(())
+    }
+
+    fn decrypt_key(&self, key: &[u8], associated_data: &[u8]) -> Result<SecretBytes> {
+        if key.len() < NONCE_LENGTH {
+            Err(Error::CorruptDatabase("token key is too short".to_owned()))?;
+        }
+
+        self.cipher()?
+            .decrypt(
+                XNonce::from_slice(&key[0..NONCE_LENGTH]),
+                Payload {
+                    msg: &key[NONCE_LENGTH..],
+                    aad: associated_data,
+                },
+            )
+            .map(SecretBytes::from)
+            // Keys of legacy databases are not covered by MAC, wrong password is detected here
+            .map_err(|_| Error::WrongPassword)
+    }
+
+    /// Changes unlock factors (and password), all token keys are re-encrypted with new master key
+    /// derived with new salt
+    pub fn rekey(
+        &mut self,
+        unlock_factors: UnlockFactors,
+        credentials: &Credentials,
+    ) -> Result<()> {
+        let secrets = self
+            .key_file
+            .tokens
+            .iter()
+            .map(|(name, token)| {
+                Ok((
+                    name.to_owned(),
+                    self.decrypt_secret(name, token)?,
+                    self.decrypt_pin(name, token)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.key_file.master_key_salt = random_bytes(SALT_LENGTH);
+        self.key_file.unlock_factors = unlock_factors;
+        self.encryption_key = Some(derive_key(&self.key_file, credentials)?);
+
+        for (name, secret, pin) in secrets {
+            let mut token = self.key_file.tokens[&name].clone();
+            self.reencrypt_token(&name, &mut token, &secret, pin.as_deref())?;
+            self.key_file.tokens.insert(name, token);
+        }
+
+        Ok(())
+    }
+
+    /// Renames token, its key is re-encrypted since it is bound to token name
+    pub fn rename_token(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        if self.key_file.tokens.contains_key(new_name) {
+            Err(Error::TokenExists(new_name.to_owned()))?;
+        }
+
+        let token = self
+            .key_file
+            .tokens
+            .get(old_name)
+            .ok_or_else(|| Error::TokenNotFound(old_name.to_owned()))?;
+
+        let secret = self.decrypt_secret(old_name, token)?;
+        let pin = self.decrypt_pin(old_name, token)?;
+
+        let mut token = token.clone();
+        self.reencrypt_token(new_name, &mut token, &secret, pin.as_deref())?;
+
+        self.key_file.tokens.remove(old_name);
+        self.key_file.tokens.insert(new_name.to_owned(), token);
+        Ok(())
+    }
+
+    /// Changes token parameters, its key is re-encrypted since it is bound to them
+    pub fn update_token<T>(
+        &mut self,
+        name: &str,
+        update: impl FnOnce(&mut Token) -> T,
+    ) -> Result<T> {
+        let token = self
+            .key_file
+            .tokens
+            .get(name)
+            .ok_or_else(|| Error::TokenNotFound(name.to_owned()))?;
+
+        let secret = self.decrypt_secret(name, token)?;
+        let pin = self.decrypt_pin(name, token)?;
+        let mut token = token.clone();
+        let result = update(&mut token);
+        self.reencrypt_token(name, &mut token, &secret, pin.as_deref())?;
+
+        self.key_file.tokens.insert(name.to_owned(), token);
+        Ok(result)
+    }
+
+    /// Calculates fingerprints of all tokens in database
+    pub fn fingerprints(&self) -> Result<BTreeMap<String, [u8; 32]>> {
+        self.key_file
+            .tokens
+            .iter()
+            .map(|(name, token)| {
+                let secret = self.decrypt_secret(name, token)?;
+                let pin = self.decrypt_pin(name, token)?;
+                Ok((
+                    name.to_owned(),
+                    token_fingerprint(token, &secret, pin.as_deref()),
+                ))
+            })
+            .collect()
+    }
+
+    /// Decrypts token secret (and PIN) and builds code generator from it
+    pub fn generator(&self, name: &str, token: &Token) -> Result<Generator> {
+        Generator::new(
+            token,
+            self.decrypt_secret(name, token)?,
+            self.decrypt_pin(name, token)?,
+        )
+    }
+
+    /// Generates current code of token with given name, counter of HOTP token is advanced, so
+    /// database has to be saved afterwards
+    pub fn generate_code(&mut self, name: &str) -> Result<String> {
+        let token = self
+            .key_file
+            .tokens
+            .get(name)
+            .ok_or_else(|| Error::TokenNotFound(name.to_owned()))?;
+
+        let code = self.generator(name, token)?.generate_current()?;
+        self.advance_counter(name)?;
+        Ok(code)
+    }
+
+    /// Moves counter of HOTP token forward after its code was used, does nothing for other tokens
+    pub fn advance_counter(&mut self, name: &str) -> Result<()> {
+        let token = self
+            .key_file
+            .tokens
+            .get_mut(name)
+            .ok_or_else(|| Error::TokenNotFound(name.to_owned()))?;
+
+        if token.token_type == TokenType::Hotp {
+            token.counter = token
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| Error::InvalidArgument("HOTP counter overflow".to_owned()))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+use otp_cli::otp::{Generator, Match, Validity};
+use otp_cli::vault::token_fingerprint;
+use otp_cli::{SecretBytes, Token, TokenAlgorithm, TokenType};
+
+fn generator(
+    token_type: TokenType,
+    algorithm: TokenAlgorithm,
+    digits: u32,
+    secret: &[u8],
+    pin: Option<&str>,
+) -> Generator {
+    let mut token = Token {
+        algorithm,
+        digits,
+        period: 30,
+        ..Default::default()
+    };
+    token.set_type(token_type);
+
+    Generator::new(
+        &token,
+        SecretBytes::from(secret.to_vec()),
+        pin.map(|pin| SecretBytes::from(pin.as_bytes().to_vec())),
+    )
+    .unwrap()
+}
+
+fn base32(secret: &str) -> Vec<u8> {
+    base32::decode(base32::Alphabet::Rfc4648 { padding: false }, secret).unwrap()
+}
+
+/// Vectors from Aegis Authenticator test suite
+#[test]
+fn yandex() {
+    let vectors = [
+        (
+            "5239",
+            "6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUA",
+            1641559648,
+            "umozdicq",
+        ),
+        (
+            "7586",
+            "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSA",
+            1581064020,
+            "oactmacq",
+        ),
+        (
+            "7586",
+            "LA2V6KMCGYMWWVEW64RNP3JA3IAAAAAAHTSA",
+            1581090810,
+            "wemdwrix",
+        ),
+        (
+            "5210481216086702",
+            "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSA",
+            1581091469,
+            "dfrpywob",
+        ),
+        (
+            "5210481216086702",
+            "JBGSAU4G7IEZG6OY4UAXX62JU4AAAAAAHTSA",
+            1581093059,
+            "vunyprpd",
+        ),
+    ];
+
+    for (pin, secret, time, code) in vectors {
+        let generator = generator(
+            TokenType::Yandex,
+            TokenAlgorithm::Sha256,
+            8,
+            &base32(secret),
+            Some(pin),
+        );
+        assert_eq!(generator.generate_at(time).unwrap(), code);
+    }
+}
+
+/// Vectors from Aegis Authenticator test suite
+#[test]
+fn motp() {
+    let vectors = [
+        ("1234", "e3152afee62599c8", 165892298, "e7d8b6"),
+        ("1234", "e3152afee62599c8", 123456789, "4ebfb2"),
+    ];
+
+    for (pin, secret, time, code) in vectors {
+        let generator = generator(
+            TokenType::Motp,
+            TokenAlgorithm::Md5,
+            6,
+            &hex::decode(secret).unwrap(),
+            Some(pin),
+        );
+        assert_eq!(generator.generate_at(time).unwrap(), code);
+    }
+}
+
+/// RFC 6238 seed and times with HMAC-MD5 instead of HMAC-SHA1. There are no published HMAC-MD5
+/// vectors, codes were calculated with Python `hmac` module and RFC 4226 truncation
+#[test]
+fn totp_md5() {
+    let vectors = [
+        (59, "78532013"),
+        (1111111109, "13672061"),
+        (1234567890, "28280616"),
+        (2000000000, "35090484"),
+    ];
+
+    let generator = generator(
+        TokenType::Totp,
+        TokenAlgorithm::Md5,
+        8,
+        b"12345678901234567890",
+        None,
+    );
+    for (time, code) in vectors {
+        assert_eq!(generator.generate_at(time).unwrap(), code);
+    }
+}
+
+#[test]
+fn pin_is_required() {
+    let token = Token {
+        token_type: TokenType::Motp,
+        digits: 6,
+        period: 10,
+        ..Default::default()
+    };
+
+    assert!(Generator::new(&token, SecretBytes::from(vec![1, 2, 3]), None).is_err());
+}
+
+fn hotp_generator(counter: u32, secret: &[u8]) -> Generator {
+    let token = Token {
+        token_type: TokenType::Hotp,
+        digits: 6,
+        counter,
+        ..Default::default()
+    };
+
+    Generator::new(&token, SecretBytes::from(secret.to_vec()), None).unwrap()
+}
+
+/// RFC 4226, appendix D
+#[test]
+fn hotp() {
+    let codes = [
+        "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+        "520489",
+    ];
+
+    for (counter, code) in codes.into_iter().enumerate() {
+        let generator = hotp_generator(counter as u32, b"12345678901234567890");
+        // HOTP codes don't depend on time
+        assert_eq!(generator.generate_at(0).unwrap(), code);
+        assert_eq!(generator.generate_at(2000000000).unwrap(), code);
+        assert_eq!(
+            generator.validity().unwrap(),
+            Validity::Counter(counter as u64)
+        );
+    }
+}
+
+/// RFC 6238, appendix B
+#[test]
+fn totp() {
+    let sha1_seed = b"12345678901234567890".as_slice();
+    let sha256_seed = b"12345678901234567890123456789012".as_slice();
+    let sha512_seed =
+        b"1234567890123456789012345678901234567890123456789012345678901234".as_slice();
+
+    let vectors = [
+        (59, "94287082", "46119246", "90693936"),
+        (1111111109, "07081804", "68084774", "25091201"),
+        (1111111111, "14050471", "67062674", "99943326"),
+        (1234567890, "89005924", "91819424", "93441116"),
+        (2000000000, "69279037", "90698825", "38618901"),
+        (20000000000, "65353130", "77737706", "47863826"),
+    ];
+
+    let sha1 = generator(TokenType::Totp, TokenAlgorithm::Sha1, 8, sha1_seed, None);
+    let sha256 = generator(
+        TokenType::Totp,
+        TokenAlgorithm::Sha256,
+        8,
+        sha256_seed,
+        None,
+    );
+    let sha512 = generator(
+        TokenType::Totp,
+        TokenAlgorithm::Sha512,
+        8,
+        sha512_seed,
+        None,
+    );
+
+    for (time, sha1_code, sha256_code, sha512_code) in vectors {
+        assert_eq!(sha1.generate_at(time).unwrap(), sha1_code);
+        assert_eq!(sha256.generate_at(time).unwrap(), sha256_code);
+        assert_eq!(sha512.generate_at(time).unwrap(), sha512_code);
+    }
+}
+
+#[test]
+fn totp_period_and_t0() {
+    let mut token = Token {
+        digits: 10,
+        period: 60,
+        ..Default::default()
+    };
+    let secret = b"12345678901234567890";
+
+    let generator = Generator::new(&token, SecretBytes::from(secret.to_vec()), None).unwrap();
+    assert_eq!(generator.generate_at(59).unwrap(), "1284755224");
+    assert_eq!(generator.generate_at(1111111109).unwrap(), "0519360094");
+
+    token.digits = 4;
+    token.t0 = 1000;
+    let generator = Generator::new(&token, SecretBytes::from(secret.to_vec()), None).unwrap();
+    assert_eq!(generator.generate_at(1059).unwrap(), "5224");
+    assert_eq!(generator.generate_at(1111112109).unwrap(), "0094");
+}
+
+/// Vectors from KeePassXC test suite
+#[test]
+fn steam() {
+    let vectors = [(1511200518, "FR8RV"), (1511200714, "9P3VP")];
+
+    let generator = generator(
+        TokenType::Steam,
+        TokenAlgorithm::Sha1,
+        5,
+        &base32("63BEDWCQZKTQWPESARIERL5DTTQFCJTK"),
+        None,
+    );
+    for (time, code) in vectors {
+        assert_eq!(generator.generate_at(time).unwrap(), code);
+    }
+}
+
+#[test]
+fn digits_are_validated() {
+    for digits in [0, 3, 11] {
+        let token = Token {
+            digits,
+            period: 30,
+            ..Default::default()
+        };
+
+        assert!(Generator::new(&token, SecretBytes::from(vec![1, 2, 3]), None).is_err());
+    }
+}
+
+#[test]
+fn fixed_shape_is_validated() {
+    for token_type in [TokenType::Steam, TokenType::Motp, TokenType::Yandex] {
+        let mut token = Token::default();
+        token.set_type(token_type);
+        token.digits = 14;
+        let pin = SecretBytes::from(b"1234".to_vec());
+
+        assert!(Generator::new(&token, SecretBytes::from(vec![1, 2, 3]), Some(pin)).is_err());
+    }
+}
+
+#[test]
+fn empty_secret_is_rejected() {
+    let token = Token {
+        digits: 6,
+        period: 30,
+        ..Default::default()
+    };
+
+    assert!(Generator::new(&token, SecretBytes::from(Vec::new()), None).is_err());
+}
+
+#[test]
+fn verify() {
+    let generator = generator(
+        TokenType::Totp,
+        TokenAlgorithm::Sha1,
+        8,
+        b"12345678901234567890",
+        None,
+    );
+
+    // 1111111109 and 1111111111 are in adjacent time steps
+    let matched = generator.verify_at("1405 0471", 1111111109, 1).unwrap();
+    assert_eq!(matched.map(|matched| matched.offset), Some(1));
+    let matched = generator.verify_at("07081804", 1111111111, 1).unwrap();
+    assert_eq!(matched.map(|matched| matched.offset), Some(-1));
+    assert_eq!(
+        generator.verify_at("07081804", 1111111111, 0).unwrap(),
+        None
+    );
+
+    let generator = hotp_generator(5, b"12345678901234567890");
+    let matched = generator.verify_at("162583", 0, 2).unwrap();
+    assert_eq!(
+        matched,
+        Some(Match {
+            offset: 2,
+            counter: 7
+        })
+    );
+    assert_eq!(generator.verify_at("520489", 0, 2).unwrap(), None);
+}
+
+#[test]
+fn offset() {
+    let generator = generator(
+        TokenType::Totp,
+        TokenAlgorithm::Sha1,
+        8,
+        b"12345678901234567890",
+        None,
+    );
+    assert_eq!(
+        generator.generate_with_offset(1111111109, 1).unwrap(),
+        "14050471"
+    );
+    assert_eq!(
+        generator.generate_with_offset(1111111111, -1).unwrap(),
+        "07081804"
+    );
+
+    let generator = hotp_generator(5, b"12345678901234567890");
+    assert_eq!(generator.generate_with_offset(0, -5).unwrap(), "755224");
+    assert!(generator.generate_with_offset(0, -6).is_err());
+}
+
+#[test]
+fn fingerprint() {
+    let token = Token {
+        issuer: String::from("GitHub"),
+        label: String::from("alice"),
+        digits: 6,
+        period: 30,
+        ..Default::default()
+    };
+    let fingerprint = token_fingerprint(&token, b"secret", None);
+
+    // Names and metadata don't affect codes
+    let renamed = Token {
+        issuer: String::from("Other"),
+        label: String::from("bob"),
+        tags: vec![String::from("work")],
+        ..token.clone()
+    };
+    assert_eq!(token_fingerprint(&renamed, b"secret", None), fingerprint);
+
+    assert_ne!(
+        token_fingerprint(&token, b"other secret", None),
+        fingerprint
+    );
+    let digits = Token {
+        digits: 8,
+        ..token.clone()
+    };
+    assert_ne!(token_fingerprint(&digits, b"secret", None), fingerprint);
+    let period = Token {
+        period: 60,
+        ..token.clone()
+    };
+    assert_ne!(token_fingerprint(&period, b"secret", None), fingerprint);
+
+    // Tokens with PIN produce different codes for different PINs
+    let motp = Token {
+        token_type: TokenType::Motp,
+        digits: 6,
+        period: 10,
+        ..Default::default()
+    };
+    assert_eq!(
+        token_fingerprint(&motp, b"secret", Some(b"1234")),
+        token_fingerprint(&motp, b"secret", Some(b"1234"))
+    );
+    assert_ne!(
+        token_fingerprint(&motp, b"secret", Some(b"1234")),
+        token_fingerprint(&motp, b"secret", Some(b"4321"))
+    );
+    // PIN is ignored for types that don't use it
+    assert_eq!(
+        token_fingerprint(&token, b"secret", Some(b"1234")),
+        fingerprint
+    );
+}